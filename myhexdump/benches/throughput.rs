@@ -0,0 +1,82 @@
+//! Dump throughput of the release binary for each layout, from a file, a
+//! mapped file and a pipe. Run with `cargo bench`, BENCH_SIZE_MB changes the input size.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const DEFAULT_SIZE_MB: usize = 64;
+const RUNS: usize = 3;
+
+/// Pseudo random bytes, so squeezing does not kick in
+fn create_input(path: &str, size: usize) -> io::Result<()> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut data: Vec<u8> = Vec::with_capacity(size);
+    while data.len() < size {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        data.extend_from_slice(&state.to_le_bytes());
+    }
+    data.truncate(size);
+    fs::write(path, data)
+}
+
+/// Best of RUNS, output goes to /dev/null
+fn run(args: &[&str], path: &str, from_pipe: bool) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_myhexdump"));
+        command.args(args).stdout(Stdio::null());
+        if from_pipe {
+            command.stdin(Stdio::piped());
+        } else {
+            command.arg(path);
+        }
+        let start = Instant::now();
+        let mut child = command.spawn().expect("failed to run myhexdump");
+        if from_pipe {
+            let data = fs::read(path).unwrap();
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(&data).unwrap();
+        }
+        let status = child.wait().unwrap();
+        assert!(status.success());
+        best = best.min(start.elapsed());
+    }
+    best
+}
+
+fn main() {
+    let size_mb: usize = env::var("BENCH_SIZE_MB")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_SIZE_MB);
+    let path = env::temp_dir().join(format!("myhexdump-bench-{}.bin", std::process::id()));
+    let path = path.to_string_lossy().into_owned();
+    create_input(&path, size_mb << 20).expect("failed to create the input file");
+
+    let cases: [(&str, &[&str], bool); 7] = [
+        ("default", &[], false),
+        ("-C", &["-C"], false),
+        ("--xxd", &["--xxd"], false),
+        ("-p", &["-p"], false),
+        ("-C --color=always", &["-C", "--color=always"], false),
+        ("-C --mmap", &["-C", "--mmap"], false),
+        ("-C from a pipe", &["-C"], true),
+    ];
+    println!("input: {} MiB", size_mb);
+    for (name, args, from_pipe) in cases {
+        let elapsed = run(args, &path, from_pipe);
+        let throughput = size_mb as f64 / elapsed.as_secs_f64();
+        println!(
+            "{:<20} {:>8.3} s {:>8.1} MiB/s",
+            name,
+            elapsed.as_secs_f64(),
+            throughput
+        );
+    }
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,78 @@
+use std::io::{stdout, IsTerminal};
+
+use clap::ValueEnum;
+
+use crate::format::is_printable;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ColorMode {
+    /// Only when stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorMode::Auto => stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// Bytes are colored by class so structure stands out in binary data
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ByteClass {
+    Null,
+    Printable,
+    Whitespace,
+    Control,
+    High,
+}
+
+impl ByteClass {
+    pub fn of(byte: u8) -> ByteClass {
+        if byte == 0 {
+            ByteClass::Null
+        } else if byte.is_ascii_whitespace() || byte == 0x0b {
+            ByteClass::Whitespace
+        } else if is_printable(byte) {
+            ByteClass::Printable
+        } else if byte.is_ascii() {
+            ByteClass::Control
+        } else {
+            ByteClass::High
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            ByteClass::Null => "#808080",
+            ByteClass::Printable => "#5fd75f",
+            ByteClass::Whitespace => "#5fafff",
+            ByteClass::Control => "#d7af00",
+            ByteClass::High => "#d75fd7",
+        }
+    }
+}
+
+/// RGB escape sequence for a "#rrggbb" color, white if it does not parse
+pub fn start_color(color: &str) -> String {
+    let channel = |idx: usize| {
+        color
+            .get(idx..idx + 2)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .unwrap_or(255)
+    };
+    if color.len() != 7 || !color.starts_with('#') {
+        eprintln!("ERROR: failed to parse color {}", color);
+        return "\x1B[38;2;255;255;255m".to_string();
+    }
+    format!("\x1B[38;2;{};{};{}m", channel(1), channel(3), channel(5))
+}
+
+pub fn stop_color() -> &'static str {
+    "\x1B[0m"
+}
@@ -0,0 +1,157 @@
+use std::cell::Cell;
+use std::io::{self, BufRead, BufReader, Read};
+use std::rc::Rc;
+
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+
+/// Longest magic number, of xz
+const MAGIC_LEN: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+/// Compression of data starting with magic, None for anything else
+pub fn detect(magic: &[u8]) -> Option<Compression> {
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        Some(Compression::Gzip)
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Compression::Xz)
+    } else if magic.len() >= 4 && magic.starts_with(b"BZh") && matches!(magic[3], b'1'..=b'9') {
+        Some(Compression::Bzip2)
+    } else {
+        None
+    }
+}
+
+/// Decompressed data of reader and the number of bytes the decoder consumed
+/// from reader so far. Data that is not compressed is read as is, without
+/// a position.
+pub struct Decoded {
+    pub reader: Box<dyn Read>,
+    pub position: Option<Rc<Cell<u64>>>,
+}
+
+/// Detect the compression of reader by its magic number and decode it
+pub fn decode(mut reader: Box<dyn Read>) -> io::Result<Decoded> {
+    // a pipe can hand out the magic number in pieces
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    reader
+        .by_ref()
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let compression = detect(&magic);
+
+    let position = Rc::new(Cell::new(0));
+    let source = CountingReader {
+        inner: BufReader::new(io::Cursor::new(magic).chain(reader)),
+        count: Rc::clone(&position),
+    };
+    let reader: Box<dyn Read> = match compression {
+        Some(Compression::Gzip) => Box::new(flate2::bufread::MultiGzDecoder::new(source)),
+        Some(Compression::Zstd) => Box::new(ZstdFrames::new(source)),
+        Some(Compression::Xz) => Box::new(lzma_rust2::XzReader::new(source, true)),
+        Some(Compression::Bzip2) => Box::new(bzip2::bufread::MultiBzDecoder::new(source)),
+        None => {
+            return Ok(Decoded {
+                reader: Box::new(source),
+                position: None,
+            })
+        }
+    };
+    Ok(Decoded {
+        reader,
+        position: Some(position),
+    })
+}
+
+/// Counts the bytes read or consumed from a buffered reader, which is exactly
+/// what a decoder on top of it used up
+struct CountingReader<R: BufRead> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: BufRead> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count.set(self.count.get() + len as u64);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.inner.consume(amount);
+        self.count.set(self.count.get() + amount as u64);
+    }
+}
+
+/// zstd frames one after the other, like zstd -d reads concatenated files.
+/// Skippable frames are passed over.
+struct ZstdFrames<R: BufRead> {
+    source: R,
+    frame: FrameDecoder,
+    in_frame: bool,
+}
+
+impl<R: BufRead> ZstdFrames<R> {
+    fn new(source: R) -> ZstdFrames<R> {
+        ZstdFrames {
+            source,
+            frame: FrameDecoder::new(),
+            in_frame: false,
+        }
+    }
+}
+
+impl<R: BufRead> Read for ZstdFrames<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.in_frame {
+                while self.frame.can_collect() < buf.len() && !self.frame.is_finished() {
+                    let wanted = buf.len() - self.frame.can_collect();
+                    self.frame
+                        .decode_blocks(&mut self.source, BlockDecodingStrategy::UptoBytes(wanted))
+                        .map_err(io::Error::other)?;
+                }
+                let len = self.frame.read(buf)?;
+                if len > 0 {
+                    return Ok(len);
+                }
+                self.in_frame = false;
+            }
+            if self.source.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
+            match self.frame.reset(&mut self.source) {
+                Ok(()) => self.in_frame = true,
+                Err(FrameDecoderError::ReadFrameHeaderError(ReadFrameHeaderError::SkipFrame {
+                    length,
+                    ..
+                })) => {
+                    let skipped =
+                        io::copy(&mut (&mut self.source).take(length as u64), &mut io::sink())?;
+                    if skipped < length as u64 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                Err(e) => return Err(io::Error::other(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+
+use crate::color::{start_color, stop_color};
+use crate::error::Error;
+use crate::format::is_printable;
+use crate::input;
+
+/// Bytes per row for each side, two 16 byte panes do not fit a terminal
+pub const DIFF_ROW_SIZE: usize = 8;
+
+const DIFF_COLOR: &str = "#ff5f5f";
+
+pub struct DiffOptions {
+    pub row_size: usize,
+    /// print only the rows that differ, with context rows around them
+    pub only_diff: bool,
+    pub context: usize,
+    pub color: bool,
+}
+
+struct DiffInput {
+    name: String,
+    size: u64,
+    reader: BufReader<fs::File>,
+}
+
+impl DiffInput {
+    fn open(name: &str, offset: u64) -> Result<DiffInput, Error> {
+        let file_error = |error| Error::File {
+            name: name.to_string(),
+            error,
+        };
+        let mut file = fs::File::open(name).map_err(file_error)?;
+        let size = input::file_size(&file);
+        if let Some(size) = size.filter(|&size| offset > size) {
+            return Err(Error::SkipPastEnd { offset, size });
+        }
+        file.seek(SeekFrom::Start(offset)).map_err(file_error)?;
+        Ok(DiffInput {
+            name: name.to_string(),
+            size: size.unwrap_or_default(),
+            reader: BufReader::new(file),
+        })
+    }
+
+    /// Fill as much of buf as possible, less only at the end of the file
+    fn read_row(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    return Err(Error::File {
+                        name: self.name.clone(),
+                        error,
+                    })
+                }
+            }
+        }
+        Ok(filled)
+    }
+}
+
+/// Differences found so far
+#[derive(Default)]
+pub struct DiffSummary {
+    pub differing_bytes: u64,
+    pub first_difference: Option<u64>,
+}
+
+struct DiffPrinter<'a> {
+    options: &'a DiffOptions,
+    /// rows waiting to be printed as leading context
+    before: VecDeque<String>,
+    /// trailing context rows still to print
+    after: usize,
+    /// index of the last row printed, to show gaps with "--"
+    last_printed: Option<u64>,
+}
+
+impl DiffPrinter<'_> {
+    fn row<W: Write>(
+        &mut self,
+        out: &mut W,
+        index: u64,
+        line: String,
+        differs: bool,
+    ) -> io::Result<()> {
+        if !self.options.only_diff {
+            return writeln!(out, "{}", line);
+        }
+        if differs {
+            let first = index - self.before.len() as u64;
+            if let Some(last) = self.last_printed {
+                if first > last + 1 {
+                    writeln!(out, "--")?;
+                }
+            }
+            for context in self.before.drain(..) {
+                writeln!(out, "{}", context)?;
+            }
+            writeln!(out, "{}", line)?;
+            self.after = self.options.context;
+            self.last_printed = Some(index);
+        } else if self.after > 0 {
+            writeln!(out, "{}", line)?;
+            self.after -= 1;
+            self.last_printed = Some(index);
+        } else if self.options.context > 0 {
+            if self.before.len() == self.options.context {
+                self.before.pop_front();
+            }
+            self.before.push_back(line);
+        }
+        Ok(())
+    }
+}
+
+fn format_pane(own: &[u8], other: &[u8], row_size: usize, color: bool) -> (String, String) {
+    let mut hex = String::new();
+    let mut ascii = String::new();
+    for idx in 0..row_size {
+        let byte = match own.get(idx) {
+            Some(byte) => *byte,
+            None => {
+                hex.push_str("   ");
+                continue;
+            }
+        };
+        let differs = other.get(idx) != Some(&byte);
+        let text = if is_printable(byte) {
+            byte as char
+        } else {
+            '.'
+        };
+        if differs && color {
+            let _ = write!(
+                hex,
+                "{}{:02x}{} ",
+                start_color(DIFF_COLOR),
+                byte,
+                stop_color()
+            );
+            let _ = write!(ascii, "{}{}{}", start_color(DIFF_COLOR), text, stop_color());
+        } else {
+            let _ = write!(hex, "{:02x} ", byte);
+            ascii.push(text);
+        }
+    }
+    (hex, ascii)
+}
+
+/// Dump two files side by side from offset, at most length bytes. Rows with
+/// differing bytes are marked with '!' and the bytes highlighted when colors
+/// are enabled. An offset past the end of either file is an error.
+pub fn diff_files<W: Write>(
+    out: &mut W,
+    name_a: &str,
+    name_b: &str,
+    offset: u64,
+    length: Option<u64>,
+    options: &DiffOptions,
+) -> Result<DiffSummary, Error> {
+    let mut input_a = DiffInput::open(name_a, offset)?;
+    let mut input_b = DiffInput::open(name_b, offset)?;
+
+    writeln!(out, "A: {} ({} bytes)", input_a.name, input_a.size).map_err(Error::Output)?;
+    writeln!(out, "B: {} ({} bytes)", input_b.name, input_b.size).map_err(Error::Output)?;
+
+    let row_size = options.row_size;
+    let mut row_a: Vec<u8> = vec![0; row_size];
+    let mut row_b: Vec<u8> = vec![0; row_size];
+    let mut summary = DiffSummary::default();
+    let mut printer = DiffPrinter {
+        options,
+        before: VecDeque::new(),
+        after: 0,
+        last_printed: None,
+    };
+
+    let mut position = offset;
+    let mut remaining = length;
+    let mut index: u64 = 0;
+    loop {
+        let wanted = match remaining {
+            Some(0) => break,
+            Some(remaining) => remaining.min(row_size as u64) as usize,
+            None => row_size,
+        };
+        let len_a = input_a.read_row(&mut row_a[..wanted])?;
+        let len_b = input_b.read_row(&mut row_b[..wanted])?;
+        if len_a == 0 && len_b == 0 {
+            break;
+        }
+        let (bytes_a, bytes_b) = (&row_a[..len_a], &row_b[..len_b]);
+
+        let mut differing = 0;
+        for idx in 0..len_a.max(len_b) {
+            if bytes_a.get(idx) != bytes_b.get(idx) {
+                if summary.first_difference.is_none() {
+                    summary.first_difference = Some(position + idx as u64);
+                }
+                differing += 1;
+            }
+        }
+        summary.differing_bytes += differing;
+
+        let (hex_a, ascii_a) = format_pane(bytes_a, bytes_b, row_size, options.color);
+        let (hex_b, ascii_b) = format_pane(bytes_b, bytes_a, row_size, options.color);
+        let marker = if differing > 0 { '!' } else { ' ' };
+        let pad = " ".repeat(row_size - len_a);
+        let line = format!(
+            "{} {:08x} | {}|{}|{} {}|{}|",
+            marker, position, hex_a, ascii_a, pad, hex_b, ascii_b
+        );
+        printer
+            .row(out, index, line, differing > 0)
+            .map_err(Error::Output)?;
+
+        position += len_a.max(len_b) as u64;
+        index += 1;
+        if let Some(remaining) = remaining.as_mut() {
+            *remaining -= wanted as u64;
+        }
+    }
+
+    match summary.first_difference {
+        Some(first) => writeln!(
+            out,
+            "{} differing bytes, first difference at offset {:#x} ({})",
+            summary.differing_bytes, first, first
+        ),
+        None => writeln!(out, "No differences"),
+    }
+    .and_then(|_| out.flush())
+    .map_err(Error::Output)?;
+    Ok(summary)
+}
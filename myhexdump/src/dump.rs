@@ -0,0 +1,228 @@
+use std::io::{self, Write};
+
+use crate::error::Error;
+use crate::format::{Layout, RowFormat};
+use crate::input::{self, Input};
+use crate::template::Annotation;
+
+/// Rows are formatted into a buffer of this size before writing it out
+const OUTPUT_BUFFER_SIZE: usize = 1 << 20;
+
+/// Writes rows in the selected layout to out, keeping track of the offset
+/// and collapsing runs of identical rows into a single `*` line like hexdump.
+/// Either dump a whole input, or feed data in pieces of any size with write,
+/// partial rows are kept until the rest of the row arrives.
+pub struct Dumper<W: Write> {
+    out: W,
+    output: Vec<u8>,
+    format: RowFormat,
+    squeeze: bool,
+    /// bytes skipped by dump, and the offset of the first row
+    start_offset: u64,
+    /// bytes dumped by dump, all of the input when None
+    length: Option<u64>,
+    offset: u64,
+    bytes_dumped: u64,
+    pending: Vec<u8>,
+    /// show the offset in the compressed input in front of every row
+    source_offsets: bool,
+    /// source offset of the piece being written and of the pending bytes
+    chunk_source: Option<u64>,
+    pending_source: Option<u64>,
+    last_row: Vec<u8>,
+    squeezing: bool,
+    /// template annotations sorted by offset, shown after the rows
+    annotations: Vec<Annotation>,
+    next_annotation: usize,
+}
+
+impl<W: Write> Dumper<W> {
+    pub fn new(out: W, format: RowFormat) -> Dumper<W> {
+        // xxd and plain hex never squeeze, to stay compatible with xxd
+        let squeeze = !matches!(format.layout(), Layout::Xxd | Layout::Plain);
+        Dumper {
+            out,
+            output: Vec::with_capacity(OUTPUT_BUFFER_SIZE + 4096),
+            format,
+            squeeze,
+            start_offset: 0,
+            length: None,
+            offset: 0,
+            bytes_dumped: 0,
+            pending: Vec::new(),
+            source_offsets: false,
+            chunk_source: None,
+            pending_source: None,
+            last_row: Vec::new(),
+            squeezing: false,
+            annotations: Vec::new(),
+            next_annotation: 0,
+        }
+    }
+
+    /// Start at offset, same as hexdump -s. dump skips offset bytes of the
+    /// input, data given to write is shown starting at offset.
+    pub fn with_offset(mut self, offset: u64) -> Dumper<W> {
+        self.start_offset = offset;
+        self.offset = offset;
+        self
+    }
+
+    /// Dump at most length bytes of the input, same as hexdump -n
+    pub fn with_length(mut self, length: Option<u64>) -> Dumper<W> {
+        self.length = length;
+        self
+    }
+
+    /// Print every row, same as hexdump -v
+    pub fn no_squeeze(mut self) -> Dumper<W> {
+        self.squeeze = false;
+        self
+    }
+
+    /// Show the annotations in a third column, rows holding one are never
+    /// squeezed
+    pub fn with_annotations(mut self, annotations: Vec<Annotation>) -> Dumper<W> {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Start every row with the offset in the underlying file its data was
+    /// decoded at, for inputs decoded from another format like compressed ones
+    pub fn with_source_offsets(mut self) -> Dumper<W> {
+        self.source_offsets = true;
+        self
+    }
+
+    pub fn row_size(&self) -> usize {
+        self.format.row_size()
+    }
+
+    /// Dump the offset/length range of input and finish
+    pub fn dump<I: Input + ?Sized>(&mut self, input: &mut I) -> Result<(), Error> {
+        input::stream_with_source(
+            input,
+            self.start_offset,
+            self.length,
+            |data, source_offset| {
+                self.chunk_source = source_offset;
+                self.write(data)
+            },
+        )?;
+        self.finish()
+    }
+
+    /// Dump the next piece of the input
+    pub fn write(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        let row_size = self.row_size();
+        if !self.pending.is_empty() {
+            let missing = row_size - self.pending.len();
+            let take = missing.min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() < row_size {
+                return Ok(());
+            }
+            let row = std::mem::take(&mut self.pending);
+            self.write_row(&row, self.pending_source);
+        }
+
+        let mut rows = data.chunks_exact(row_size);
+        for row in rows.by_ref() {
+            self.write_row(row, self.chunk_source);
+            if self.output.len() >= OUTPUT_BUFFER_SIZE {
+                self.flush().map_err(Error::Output)?;
+            }
+        }
+        if self.pending.is_empty() {
+            self.pending_source = self.chunk_source;
+        }
+        self.pending.extend_from_slice(rows.remainder());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.write_all(&self.output)?;
+        self.output.clear();
+        Ok(())
+    }
+
+    /// Annotations starting inside the row at the current offset
+    fn row_annotations(&mut self, row_len: usize) -> Vec<&str> {
+        let end = self.offset + row_len as u64;
+        let first = self.next_annotation;
+        while self
+            .annotations
+            .get(self.next_annotation)
+            .is_some_and(|annotation| annotation.offset < end)
+        {
+            self.next_annotation += 1;
+        }
+        // the ones before the current offset were skipped over with -s
+        self.annotations[first..self.next_annotation]
+            .iter()
+            .filter(|annotation| annotation.offset >= self.offset)
+            .map(|annotation| annotation.text.as_str())
+            .collect()
+    }
+
+    fn write_row(&mut self, row: &[u8], source_offset: Option<u64>) {
+        let notes = if self.annotations.is_empty() {
+            String::new()
+        } else {
+            self.row_annotations(row.len()).join("; ")
+        };
+        let is_repeated = self.squeeze
+            && notes.is_empty()
+            && row.len() == self.row_size()
+            && row == self.last_row;
+        if is_repeated {
+            if !self.squeezing {
+                self.output.extend_from_slice(b"*\n");
+                self.squeezing = true;
+            }
+        } else {
+            if self.source_offsets {
+                match source_offset {
+                    Some(source_offset) => {
+                        let _ = write!(self.output, "[{:08x}] ", source_offset);
+                    }
+                    None => self.output.extend_from_slice(b"[--------] "),
+                }
+            }
+            self.format.write_row(&mut self.output, self.offset, row);
+            if !notes.is_empty() {
+                self.output.extend_from_slice(b"  ");
+                self.output.extend_from_slice(notes.as_bytes());
+            }
+            if self.format.layout() != Layout::Custom {
+                self.output.push(b'\n');
+            }
+            self.squeezing = false;
+            self.last_row.clear();
+            self.last_row.extend_from_slice(row);
+        }
+        self.offset += row.len() as u64;
+        self.bytes_dumped += row.len() as u64;
+    }
+
+    /// Print what is left of the last row and the final offset line. Layouts
+    /// without one still get it when the dump ended inside squeezed rows,
+    /// otherwise the length would be lost.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if !self.pending.is_empty() {
+            let row = std::mem::take(&mut self.pending);
+            self.write_row(&row, self.pending_source);
+        }
+        if self.bytes_dumped != 0
+            && !self.format.write_end(&mut self.output, self.offset)
+            && self.squeezing
+        {
+            self.output
+                .extend_from_slice(format!("{:08x}\n", self.offset).as_bytes());
+        }
+        self.flush()
+            .and_then(|_| self.out.flush())
+            .map_err(Error::Output)
+    }
+}
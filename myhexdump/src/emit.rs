@@ -0,0 +1,124 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+
+/// Bytes per line of the C and Rust arrays, same as xxd -i
+const ARRAY_COLS: usize = 12;
+const PYTHON_COLS: usize = 16;
+/// Line length of MIME base64
+const BASE64_LINE: usize = 76;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Language {
+    /// unsigned char array and length, like xxd -i
+    C,
+    /// const array of u8
+    Rust,
+    /// bytes literal
+    Python,
+    /// base64 lines of 76 characters
+    Base64,
+}
+
+/// Variable name for a file name, non alphanumeric characters become '_'
+/// like xxd -i does
+pub fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert_str(0, "__");
+    }
+    identifier
+}
+
+/// Parse --name, which has to be a C identifier: letters, digits and '_',
+/// not starting with a digit
+pub fn parse_name(value: &str) -> Result<String, String> {
+    let valid = value.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!(
+            "[{}] is not a valid identifier, use letters, digits and '_' not starting with a digit",
+            value
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Write data as source code defining name
+pub fn emit<W: Write>(out: &mut W, language: Language, name: &str, data: &[u8]) -> io::Result<()> {
+    match language {
+        Language::C => emit_c(out, name, data),
+        Language::Rust => emit_rust(out, name, data),
+        Language::Python => emit_python(out, name, data),
+        Language::Base64 => emit_base64(out, data),
+    }
+}
+
+fn emit_c<W: Write>(out: &mut W, name: &str, data: &[u8]) -> io::Result<()> {
+    writeln!(out, "unsigned char {}[] = {{", name)?;
+    let rows = data.chunks(ARRAY_COLS).count();
+    for (idx, row) in data.chunks(ARRAY_COLS).enumerate() {
+        let bytes: Vec<String> = row.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+        let separator = if idx + 1 == rows { "" } else { "," };
+        writeln!(out, "  {}{}", bytes.join(", "), separator)?;
+    }
+    writeln!(out, "}};")?;
+    writeln!(out, "unsigned int {}_len = {};", name, data.len())
+}
+
+fn emit_rust<W: Write>(out: &mut W, name: &str, data: &[u8]) -> io::Result<()> {
+    writeln!(
+        out,
+        "pub const {}: [u8; {}] = [",
+        name.to_uppercase(),
+        data.len()
+    )?;
+    for row in data.chunks(ARRAY_COLS) {
+        let bytes: Vec<String> = row.iter().map(|byte| format!("0x{:02x},", byte)).collect();
+        writeln!(out, "    {}", bytes.join(" "))?;
+    }
+    writeln!(out, "];")
+}
+
+fn emit_python<W: Write>(out: &mut W, name: &str, data: &[u8]) -> io::Result<()> {
+    if data.is_empty() {
+        return writeln!(out, "{} = b\"\"", name);
+    }
+    writeln!(out, "{} = (", name)?;
+    for row in data.chunks(PYTHON_COLS) {
+        let bytes: String = row.iter().map(|byte| format!("\\x{:02x}", byte)).collect();
+        writeln!(out, "    b\"{}\"", bytes)?;
+    }
+    writeln!(out, ")")
+}
+
+fn base64(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let value = (group[0] as u32) << 16
+            | (group.get(1).copied().unwrap_or(0) as u32) << 8
+            | group.get(2).copied().unwrap_or(0) as u32;
+        for idx in 0..4 {
+            if idx <= group.len() {
+                encoded.push(BASE64_ALPHABET[(value >> (18 - idx * 6)) as usize & 0x3f]);
+            } else {
+                encoded.push(b'=');
+            }
+        }
+    }
+    encoded
+}
+
+fn emit_base64<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    for line in base64(data).chunks(BASE64_LINE) {
+        out.write_all(line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
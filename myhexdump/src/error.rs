@@ -0,0 +1,85 @@
+use std::fmt;
+use std::io;
+
+/// Errors stopping a dump, or any other run of the binary
+#[derive(Debug)]
+pub enum Error {
+    /// reading the input failed
+    Input(io::Error),
+    /// writing the dump failed
+    Output(io::Error),
+    /// opening or reading the named file failed
+    File { name: String, error: io::Error },
+    /// any other failure, described by the message
+    Message(String),
+    /// the offset is past the end of the input, which held size bytes
+    SkipPastEnd { offset: u64, size: u64 },
+    /// some inputs could not be read, their errors were printed as they
+    /// happened and the others were still dumped
+    InputsFailed,
+    /// the diffed files differ, like cmp exits 1
+    Differ,
+    /// the pattern was not found, like grep exits 1
+    NotFound,
+    /// a failure of a diff or search, which exit 2 like cmp and grep do
+    Trouble(Box<Error>),
+}
+
+impl Error {
+    /// A closed pipe (like `| head`) ends the dump early but is not a failure
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            Error::Output(e) => e.kind() == io::ErrorKind::BrokenPipe,
+            Error::Trouble(e) => e.is_broken_pipe(),
+            _ => false,
+        }
+    }
+
+    /// Errors that end the run without a message of their own
+    pub fn is_silent(&self) -> bool {
+        match self {
+            Error::InputsFailed | Error::Differ | Error::NotFound => true,
+            Error::Trouble(e) => e.is_silent(),
+            _ => self.is_broken_pipe(),
+        }
+    }
+
+    /// Exit status of the binary for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            _ if self.is_broken_pipe() => 0,
+            Error::Trouble(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Input(e) => write!(f, "Failed to read input ({})", e),
+            Error::Output(e) => write!(f, "Failed to write output ({})", e),
+            Error::File { name, error } => write!(f, "Failed to read file [{}] ({})", name, error),
+            Error::Message(message) => write!(f, "{}", message),
+            Error::SkipPastEnd { offset, size } => write!(
+                f,
+                "Skipped[{}] past the end of input size[{}]",
+                offset, size
+            ),
+            Error::InputsFailed => write!(f, "Failed to read some of the inputs"),
+            Error::Differ => write!(f, "The files differ"),
+            Error::NotFound => write!(f, "No matches"),
+            Error::Trouble(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Input(e) | Error::Output(e) | Error::File { error: e, .. } => Some(e),
+            Error::Trouble(e) => e.source(),
+            _ => None,
+        }
+    }
+}
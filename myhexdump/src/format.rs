@@ -0,0 +1,488 @@
+use std::fmt::{Display, LowerExp};
+use std::io::Write;
+
+use clap::ValueEnum;
+
+use crate::color::{self, ByteClass};
+
+mod custom;
+pub use custom::{parse_format, CustomFormat, Format};
+
+/// Bytes per row for every layout except xxd, where it can be changed
+pub const ROW_SIZE: usize = 16;
+
+const XXD_DEFAULT_GROUP: usize = 2;
+const PLAIN_DEFAULT_COLS: usize = 30;
+/// Room taken by each word of the 16-bit layouts
+const WORD_WIDTH: usize = 8;
+
+/// Lowercase hex digits of every byte, formatting is a table lookup
+const HEX_DIGITS: [[u8; 2]; 256] = {
+    let digits = b"0123456789abcdef";
+    let mut table = [[0; 2]; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        table[idx] = [digits[idx >> 4], digits[idx & 0xf]];
+        idx += 1;
+    }
+    table
+};
+
+/// Character shown in the ascii pane for every byte
+const ASCII_CHARS: [u8; 256] = {
+    let mut table = [b'.'; 256];
+    let mut idx = 32;
+    while idx <= 126 {
+        table[idx] = idx as u8;
+        idx += 1;
+    }
+    table
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// How the words of --group are decoded in the column after the ascii pane
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Decode {
+    Signed,
+    Unsigned,
+    /// IEEE 754, words of 4 or 8 bytes
+    Float,
+}
+
+/// The hex pane shows words of size bytes in the byte order of endian
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Words {
+    pub size: usize,
+    pub endian: Endian,
+    pub decode: Option<Decode>,
+}
+
+/// Word size of --group
+pub fn parse_group(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(size @ (1 | 2 | 4 | 8)) => Ok(size),
+        _ => Err(format!("[{}] is not a word size, use 1, 2, 4 or 8", text)),
+    }
+}
+
+/// Floats in plain notation unless that gets too long
+pub fn format_float<T: Display + LowerExp>(value: T) -> String {
+    let text = format!("{}", value);
+    if text.len() > 14 {
+        format!("{:.6e}", value)
+    } else {
+        text
+    }
+}
+
+impl Words {
+    pub fn new(size: usize, endian: Endian, decode: Option<Decode>) -> Result<Words, String> {
+        if decode == Some(Decode::Float) && size != 4 && size != 8 {
+            return Err("Decoding floats needs words of 4 or 8 bytes".to_string());
+        }
+        Ok(Words {
+            size,
+            endian,
+            decode,
+        })
+    }
+
+    /// Room taken by a decoded value, enough for the widest one
+    fn value_width(&self) -> usize {
+        match (self.decode, self.size) {
+            (Some(Decode::Float), _) => 14,
+            (Some(Decode::Unsigned), 1) => 3,
+            (Some(Decode::Unsigned), 2) => 5,
+            (Some(Decode::Unsigned), 4) => 10,
+            (Some(Decode::Signed), 1) => 4,
+            (Some(Decode::Signed), 2) => 6,
+            (Some(Decode::Signed), 4) => 11,
+            _ => 20,
+        }
+    }
+
+    fn value(&self, word: &[u8]) -> u64 {
+        let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+        match self.endian {
+            Endian::Little => word.iter().rev().fold(0, fold),
+            Endian::Big => word.iter().fold(0, fold),
+        }
+    }
+
+    fn decode(&self, word: &[u8]) -> String {
+        let value = self.value(word);
+        let bits = self.size as u32 * 8;
+        match self.decode {
+            Some(Decode::Unsigned) => value.to_string(),
+            Some(Decode::Signed) => {
+                let shift = 64 - bits;
+                ((value << shift) as i64 >> shift).to_string()
+            }
+            Some(Decode::Float) if bits == 32 => format_float(f32::from_bits(value as u32)),
+            Some(Decode::Float) => format_float(f64::from_bits(value)),
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    /// Our own `offset | hex | |ascii|` layout
+    Default,
+    /// hexdump -C
+    Canonical,
+    /// hexdump -x
+    Hex16,
+    /// hexdump -o
+    Octal16,
+    /// hexdump -d
+    Decimal16,
+    /// xxd
+    Xxd,
+    /// xxd -p, continuous hex without offsets
+    Plain,
+    /// hexdump -e format strings
+    Custom,
+}
+
+impl Layout {
+    /// The hexdump -e formats giving the same output, each one restarts at
+    /// the beginning of the row. These layouts are formatted by hand because
+    /// it is several times faster.
+    pub fn format_strings(&self) -> &'static [&'static str] {
+        match self {
+            Layout::Default => &[
+                r#""%08.8_ax | " 8/1 "%02x " " " 8/1 "%02x ""#,
+                r#"" |" 16/1 "%_p" "|\n""#,
+            ],
+            Layout::Canonical => &[
+                r#""%08.8_ax  " 8/1 "%02x " " " 8/1 "%02x ""#,
+                r#"" |" 16/1 "%_p" "|\n""#,
+                r#""%08.8_Ax\n""#,
+            ],
+            Layout::Hex16 => &[r#""%07.7_ax " 8/2 "   %04x " "\n""#, r#""%07.7_Ax\n""#],
+            Layout::Octal16 => &[r#""%07.7_ax " 8/2 " %06o " "\n""#, r#""%07.7_Ax\n""#],
+            Layout::Decimal16 => &[r#""%07.7_ax " 8/2 "  %05u " "\n""#, r#""%07.7_Ax\n""#],
+            Layout::Xxd | Layout::Plain | Layout::Custom => &[],
+        }
+    }
+}
+
+pub struct RowFormat {
+    layout: Layout,
+    cols: usize,
+    group: usize,
+    /// color escape of every byte, empty when colors are off
+    palette: Vec<String>,
+    custom: Option<CustomFormat>,
+    words: Option<Words>,
+}
+
+pub fn is_printable(byte: u8) -> bool {
+    (32..=126).contains(&byte)
+}
+
+/// Offset in hex, zero padded to width digits
+fn push_offset(out: &mut Vec<u8>, offset: u64, width: usize) {
+    let digits = (64 - offset.leading_zeros() as usize)
+        .div_ceil(4)
+        .max(width);
+    for idx in (0..digits).rev() {
+        out.push(HEX_DIGITS[((offset >> (idx * 4)) & 0xf) as usize][1]);
+    }
+}
+
+/// 16 bit words in host (little endian) order, an odd trailing byte is
+/// zero padded like hexdump does
+fn to_words(bytes: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    bytes.chunks(2).map(|pair| {
+        let high = pair.get(1).copied().unwrap_or(0);
+        u16::from_le_bytes([pair[0], high])
+    })
+}
+
+impl RowFormat {
+    pub fn new(layout: Layout) -> RowFormat {
+        let cols = if layout == Layout::Plain {
+            PLAIN_DEFAULT_COLS
+        } else {
+            ROW_SIZE
+        };
+        RowFormat {
+            layout,
+            cols,
+            group: XXD_DEFAULT_GROUP,
+            palette: Vec::new(),
+            custom: None,
+            words: None,
+        }
+    }
+
+    /// Rows are the blocks of the -e formats
+    pub fn custom(format: CustomFormat) -> RowFormat {
+        RowFormat {
+            layout: Layout::Custom,
+            cols: format.block_size(),
+            group: XXD_DEFAULT_GROUP,
+            palette: Vec::new(),
+            custom: Some(format),
+            words: None,
+        }
+    }
+
+    /// Color bytes by class in the hex and ascii panes. The 16-bit word
+    /// layouts mix two bytes per value and are never colored.
+    pub fn with_color(mut self, enabled: bool) -> RowFormat {
+        self.palette = if enabled {
+            (0..=255u8)
+                .map(|byte| color::start_color(ByteClass::of(byte).color()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self
+    }
+
+    /// xxd -c COLS -g GROUPSIZE, a group size of 0 means no grouping.
+    /// Only the xxd and plain layouts can change them.
+    pub fn with_xxd_options(mut self, cols: Option<usize>, group: Option<usize>) -> RowFormat {
+        if self.layout != Layout::Xxd && self.layout != Layout::Plain {
+            return self;
+        }
+        if let Some(cols) = cols {
+            self.cols = cols.max(1);
+        }
+        if let Some(group) = group {
+            self.group = group;
+        }
+        self
+    }
+
+    /// Show the hex pane as words, and optionally their values after the
+    /// ascii pane. Only the default and canonical layouts have words.
+    pub fn with_words(mut self, words: Option<Words>) -> RowFormat {
+        if self.layout == Layout::Default || self.layout == Layout::Canonical {
+            self.words = words;
+        }
+        self
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn row_size(&self) -> usize {
+        self.cols
+    }
+
+    fn push_hex(&self, out: &mut Vec<u8>, byte: u8) {
+        if self.palette.is_empty() {
+            out.extend_from_slice(&HEX_DIGITS[byte as usize]);
+        } else {
+            out.extend_from_slice(self.palette[byte as usize].as_bytes());
+            out.extend_from_slice(&HEX_DIGITS[byte as usize]);
+            out.extend_from_slice(color::stop_color().as_bytes());
+        }
+    }
+
+    fn push_ascii(&self, out: &mut Vec<u8>, bytes: &[u8]) {
+        if self.palette.is_empty() {
+            out.extend(bytes.iter().map(|byte| ASCII_CHARS[*byte as usize]));
+            return;
+        }
+        for byte in bytes {
+            out.extend_from_slice(self.palette[*byte as usize].as_bytes());
+            out.push(ASCII_CHARS[*byte as usize]);
+            out.extend_from_slice(color::stop_color().as_bytes());
+        }
+    }
+
+    /// Append one row to out, without the newline except for custom formats
+    /// which print their own
+    pub fn write_row(&self, out: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+        match self.layout {
+            Layout::Default => self.write_default(out, offset, bytes),
+            Layout::Canonical => self.write_canonical(out, offset, bytes),
+            Layout::Hex16 => write_words(out, offset, bytes, |out, word| {
+                let _ = write!(out, "   {:04x} ", word);
+            }),
+            Layout::Octal16 => write_words(out, offset, bytes, |out, word| {
+                let _ = write!(out, " {:06o} ", word);
+            }),
+            Layout::Decimal16 => write_words(out, offset, bytes, |out, word| {
+                let _ = write!(out, "  {:05} ", word);
+            }),
+            Layout::Xxd => self.write_xxd(out, offset, bytes),
+            Layout::Plain => {
+                for byte in bytes {
+                    self.push_hex(out, *byte);
+                }
+            }
+            Layout::Custom => {
+                if let Some(custom) = &self.custom {
+                    custom.write_block(out, offset, bytes);
+                }
+            }
+        }
+    }
+
+    /// hexdump style layouts end with a line holding the final offset,
+    /// custom formats with their %_A units. Returns false for the layouts
+    /// without an end.
+    pub fn write_end(&self, out: &mut Vec<u8>, offset: u64) -> bool {
+        match self.layout {
+            Layout::Canonical => push_offset(out, offset, 8),
+            Layout::Hex16 | Layout::Octal16 | Layout::Decimal16 => push_offset(out, offset, 7),
+            Layout::Custom => {
+                if let Some(custom) = &self.custom {
+                    custom.write_end(out, offset);
+                }
+                return true;
+            }
+            Layout::Default | Layout::Xxd | Layout::Plain => return false,
+        }
+        out.push(b'\n');
+        true
+    }
+
+    fn write_default(&self, out: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+        if let Some(words) = &self.words {
+            return self.write_grouped(words, out, offset, bytes, b" | ");
+        }
+        push_offset(out, offset, 8);
+        out.extend_from_slice(b" | ");
+        for (idx, byte) in bytes.iter().enumerate() {
+            self.push_hex(out, *byte);
+            out.push(b' ');
+            if idx == 7 {
+                out.push(b' ');
+            }
+        }
+        if bytes.len() < ROW_SIZE {
+            // we ended with a partial row, pad so the ascii text lines up
+            out.resize(out.len() + (ROW_SIZE - bytes.len()) * 3, b' ');
+            if bytes.len() < 8 {
+                // add extra space to account for extra space in the middle of the line
+                out.push(b' ');
+            }
+        }
+        out.extend_from_slice(b" |");
+        self.push_ascii(out, bytes);
+        out.push(b'|');
+    }
+
+    fn write_canonical(&self, out: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+        if let Some(words) = &self.words {
+            return self.write_grouped(words, out, offset, bytes, b"  ");
+        }
+        push_offset(out, offset, 8);
+        out.extend_from_slice(b"  ");
+        for idx in 0..ROW_SIZE {
+            match bytes.get(idx) {
+                Some(byte) => {
+                    self.push_hex(out, *byte);
+                    out.push(b' ');
+                }
+                None => out.extend_from_slice(b"   "),
+            }
+            if idx == 7 {
+                out.push(b' ');
+            }
+        }
+        out.extend_from_slice(b" |");
+        self.push_ascii(out, bytes);
+        out.push(b'|');
+    }
+
+    /// The default or canonical row with the hex pane in words. Bytes missing
+    /// from the last word are blank, and it is not decoded.
+    fn write_grouped(
+        &self,
+        words: &Words,
+        out: &mut Vec<u8>,
+        offset: u64,
+        bytes: &[u8],
+        separator: &[u8],
+    ) {
+        push_offset(out, offset, 8);
+        out.extend_from_slice(separator);
+        for start in (0..ROW_SIZE).step_by(words.size) {
+            if start == ROW_SIZE / 2 {
+                out.push(b' ');
+            }
+            for idx in 0..words.size {
+                let idx = match words.endian {
+                    Endian::Little => start + words.size - 1 - idx,
+                    Endian::Big => start + idx,
+                };
+                match bytes.get(idx) {
+                    Some(byte) => self.push_hex(out, *byte),
+                    None => out.extend_from_slice(b"  "),
+                }
+            }
+            out.push(b' ');
+        }
+        out.extend_from_slice(b" |");
+        self.push_ascii(out, bytes);
+        out.push(b'|');
+
+        if words.decode.is_some() {
+            // line the values up with the ones of the full rows
+            out.resize(out.len() + ROW_SIZE - bytes.len(), b' ');
+            for word in bytes.chunks_exact(words.size) {
+                let _ = write!(
+                    out,
+                    " {:>width$}",
+                    words.decode(word),
+                    width = words.value_width()
+                );
+            }
+        }
+    }
+
+    fn write_xxd(&self, out: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+        let group = if self.group == 0 {
+            self.cols
+        } else {
+            self.group
+        };
+        let groups = self.cols.div_ceil(group);
+        let hex_width = self.cols * 2 + groups - 1;
+
+        push_offset(out, offset, 8);
+        out.extend_from_slice(b": ");
+        // escape sequences take no room on screen, count the width by hand
+        let mut width = 0;
+        for (idx, byte) in bytes.iter().enumerate() {
+            if idx != 0 && idx % group == 0 {
+                out.push(b' ');
+                width += 1;
+            }
+            self.push_hex(out, *byte);
+            width += 2;
+        }
+        out.resize(out.len() + hex_width.saturating_sub(width) + 2, b' ');
+        self.push_ascii(out, bytes);
+    }
+}
+
+fn write_words<F: Fn(&mut Vec<u8>, u16)>(
+    out: &mut Vec<u8>,
+    offset: u64,
+    bytes: &[u8],
+    word_format: F,
+) {
+    push_offset(out, offset, 7);
+    out.push(b' ');
+    for word in to_words(bytes) {
+        word_format(out, word);
+    }
+    // like hexdump, the missing words of the last row are blank
+    let missing = ROW_SIZE / 2 - bytes.len().div_ceil(2);
+    out.resize(out.len() + missing * WORD_WIDTH, b' ');
+}
@@ -0,0 +1,630 @@
+//! hexdump -e format strings: `[iterations][/byte_count] "format"` units
+//! with printf style conversions applied to each block of input.
+
+use super::is_printable;
+
+/// Names shown by %_u for the control characters
+const CONTROL_NAMES: [&str; 32] = [
+    "nul", "soh", "stx", "etx", "eot", "enq", "ack", "bel", "bs", "ht", "lf", "vt", "ff", "cr",
+    "so", "si", "dle", "dc1", "dc2", "dc3", "dc4", "nak", "syn", "etb", "can", "em", "sub", "esc",
+    "fs", "gs", "rs", "us",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Conversion {
+    /// %d %i %o %u %x %X
+    Int(u8),
+    /// %e %E %f %g %G
+    Float(u8),
+    /// %c
+    Char,
+    /// %_c, C escapes or octal for the non printable bytes
+    Escaped,
+    /// %_p, printable chars or '.'
+    Printable,
+    /// %_u, names for the control chars
+    AsciiName,
+    /// %_a[dox], offset of the next byte
+    Offset(u8),
+    /// %_A[dox], offset after the last byte, printed once at the end
+    EndOffset(u8),
+}
+
+impl Conversion {
+    fn consumes_input(&self) -> bool {
+        !matches!(self, Conversion::Offset(_) | Conversion::EndOffset(_))
+    }
+
+    fn default_size(&self) -> usize {
+        match self {
+            Conversion::Int(_) => 4,
+            Conversion::Float(_) => 8,
+            Conversion::Offset(_) | Conversion::EndOffset(_) => 0,
+            _ => 1,
+        }
+    }
+
+    fn allows_size(&self, size: usize) -> bool {
+        match self {
+            Conversion::Int(_) => matches!(size, 1 | 2 | 4 | 8),
+            Conversion::Float(_) => matches!(size, 4 | 8),
+            _ => size == 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Flags {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Spec {
+    flags: Flags,
+    width: usize,
+    precision: Option<usize>,
+    conversion: Conversion,
+    /// bytes of input consumed
+    size: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Text(Vec<u8>),
+    Convert(Spec),
+}
+
+#[derive(Clone, Debug)]
+struct Unit {
+    reps: usize,
+    /// an iteration count was given, the unit is not stretched to fill the block
+    explicit_reps: bool,
+    byte_count: Option<usize>,
+    pieces: Vec<Piece>,
+}
+
+impl Unit {
+    fn specs(&self) -> impl Iterator<Item = &Spec> {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Convert(spec) => Some(spec),
+            Piece::Text(_) => None,
+        })
+    }
+
+    /// Bytes consumed by one iteration
+    fn size(&self) -> usize {
+        let size = self.specs().map(|spec| spec.size).sum();
+        match self.byte_count {
+            Some(count) if size == 0 => count,
+            _ => size,
+        }
+    }
+
+    /// Units with %_A are only printed once, after all the input
+    fn is_end(&self) -> bool {
+        self.specs()
+            .any(|spec| matches!(spec.conversion, Conversion::EndOffset(_)))
+    }
+}
+
+/// One -e argument, its units are applied to every block from its start
+#[derive(Clone, Debug)]
+pub struct Format {
+    units: Vec<Unit>,
+}
+
+impl Format {
+    fn size(&self) -> usize {
+        self.units
+            .iter()
+            .filter(|unit| !unit.is_end())
+            .map(|unit| unit.reps * unit.size())
+            .sum()
+    }
+}
+
+/// All the -e formats, input is handed out in blocks of the size of the
+/// largest one
+#[derive(Clone, Debug)]
+pub struct CustomFormat {
+    formats: Vec<Format>,
+    block_size: usize,
+}
+
+fn read_number(bytes: &[u8], idx: &mut usize) -> Option<usize> {
+    let start = *idx;
+    while bytes.get(*idx).is_some_and(|byte| byte.is_ascii_digit()) {
+        *idx += 1;
+    }
+    std::str::from_utf8(&bytes[start..*idx]).ok()?.parse().ok()
+}
+
+fn skip_whitespace(bytes: &[u8], idx: &mut usize) {
+    while bytes
+        .get(*idx)
+        .is_some_and(|byte| byte.is_ascii_whitespace())
+    {
+        *idx += 1;
+    }
+}
+
+/// Quoted string starting after the opening quote, with its escapes resolved
+fn read_quoted(bytes: &[u8], idx: &mut usize) -> Result<Vec<u8>, String> {
+    let mut text = Vec::new();
+    loop {
+        let byte = match bytes.get(*idx) {
+            Some(byte) => *byte,
+            None => return Err("unterminated quoted string in format".to_string()),
+        };
+        *idx += 1;
+        match byte {
+            b'"' => return Ok(text),
+            b'\\' => {
+                let escaped = bytes.get(*idx).copied().unwrap_or(b'\\');
+                *idx += 1;
+                text.push(match escaped {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b'f' => 0x0c,
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'v' => 0x0b,
+                    b'0' => 0,
+                    other => other,
+                });
+            }
+            byte => text.push(byte),
+        }
+    }
+}
+
+/// Split the text of a unit into literal text and conversions
+fn parse_pieces(text: &[u8], byte_count: Option<usize>) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = Vec::new();
+    let mut idx = 0;
+    while idx < text.len() {
+        if text[idx] != b'%' {
+            literal.push(text[idx]);
+            idx += 1;
+            continue;
+        }
+        idx += 1;
+        if text.get(idx) == Some(&b'%') {
+            literal.push(b'%');
+            idx += 1;
+            continue;
+        }
+
+        let mut flags = Flags::default();
+        while let Some(flag) = text.get(idx) {
+            match flag {
+                b'-' => flags.left = true,
+                b'0' => flags.zero = true,
+                b'+' => flags.plus = true,
+                b' ' => flags.space = true,
+                b'#' => flags.alternate = true,
+                _ => break,
+            }
+            idx += 1;
+        }
+        let width = read_number(text, &mut idx).unwrap_or(0);
+        let precision = if text.get(idx) == Some(&b'.') {
+            idx += 1;
+            Some(read_number(text, &mut idx).unwrap_or(0))
+        } else {
+            None
+        };
+
+        let conversion = match text.get(idx) {
+            Some(kind @ (b'd' | b'i' | b'o' | b'u' | b'x' | b'X')) => Conversion::Int(*kind),
+            Some(kind @ (b'e' | b'E' | b'f' | b'g' | b'G')) => Conversion::Float(*kind),
+            Some(b'c') => Conversion::Char,
+            Some(b'_') => {
+                idx += 1;
+                match (text.get(idx), text.get(idx + 1)) {
+                    (Some(b'c'), _) => Conversion::Escaped,
+                    (Some(b'p'), _) => Conversion::Printable,
+                    (Some(b'u'), _) => Conversion::AsciiName,
+                    (Some(b'a'), Some(radix @ (b'd' | b'o' | b'x'))) => {
+                        idx += 1;
+                        Conversion::Offset(*radix)
+                    }
+                    (Some(b'A'), Some(radix @ (b'd' | b'o' | b'x'))) => {
+                        idx += 1;
+                        Conversion::EndOffset(*radix)
+                    }
+                    (Some(b'a' | b'A'), _) => {
+                        return Err(
+                            "offset conversions need a radix: %_ad, %_ao or %_ax".to_string()
+                        )
+                    }
+                    _ => return Err("unsupported conversion after %_".to_string()),
+                }
+            }
+            Some(other) => {
+                return Err(format!(
+                    "unsupported conversion %{} in format",
+                    *other as char
+                ))
+            }
+            None => return Err("format ends in the middle of a conversion".to_string()),
+        };
+        idx += 1;
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(std::mem::take(&mut literal)));
+        }
+        pieces.push(Piece::Convert(Spec {
+            flags,
+            width,
+            precision,
+            conversion,
+            size: conversion.default_size(),
+        }));
+    }
+    if !literal.is_empty() {
+        pieces.push(Piece::Text(literal));
+    }
+
+    let mut consumers = pieces.iter_mut().filter_map(|piece| match piece {
+        Piece::Convert(spec) if spec.conversion.consumes_input() => Some(spec),
+        _ => None,
+    });
+    if let Some(count) = byte_count {
+        if let Some(spec) = consumers.next() {
+            spec.size = count;
+            if consumers.next().is_some() {
+                return Err("a byte count needs a format with a single conversion".to_string());
+            }
+        }
+    }
+    for piece in pieces.iter() {
+        if let Piece::Convert(spec) = piece {
+            if spec.conversion.consumes_input() && !spec.conversion.allows_size(spec.size) {
+                return Err(format!(
+                    "byte count {} does not fit the conversion",
+                    spec.size
+                ));
+            }
+        }
+    }
+    Ok(pieces)
+}
+
+/// Parse one -e argument: `[iterations][/byte_count] "format"` units
+pub fn parse_format(value: &str) -> Result<Format, String> {
+    let bytes = value.as_bytes();
+    let mut units = Vec::new();
+    let mut idx = 0;
+    loop {
+        skip_whitespace(bytes, &mut idx);
+        if idx == bytes.len() {
+            break;
+        }
+        let reps = read_number(bytes, &mut idx);
+        skip_whitespace(bytes, &mut idx);
+        let byte_count = if bytes.get(idx) == Some(&b'/') {
+            idx += 1;
+            skip_whitespace(bytes, &mut idx);
+            match read_number(bytes, &mut idx) {
+                Some(count) if count > 0 => Some(count),
+                _ => return Err(format!("missing byte count after / in [{}]", value)),
+            }
+        } else {
+            None
+        };
+        skip_whitespace(bytes, &mut idx);
+        if bytes.get(idx) != Some(&b'"') {
+            return Err(format!("expected a quoted format string in [{}]", value));
+        }
+        idx += 1;
+        let text = read_quoted(bytes, &mut idx)?;
+        units.push(Unit {
+            reps: reps.unwrap_or(1),
+            explicit_reps: reps.is_some(),
+            byte_count,
+            pieces: parse_pieces(&text, byte_count)?,
+        });
+    }
+    if units.is_empty() {
+        return Err("empty format string".to_string());
+    }
+    Ok(Format { units })
+}
+
+impl CustomFormat {
+    pub fn new(mut formats: Vec<Format>) -> Result<CustomFormat, String> {
+        let block_size = formats.iter().map(Format::size).max().unwrap_or(0);
+        if block_size == 0 {
+            return Err("the format strings do not consume any input".to_string());
+        }
+        // like hexdump, a last unit without iteration count is repeated to
+        // fill the block
+        for format in formats.iter_mut() {
+            let size = format.size();
+            if let Some(last) = format.units.iter_mut().rev().find(|unit| !unit.is_end()) {
+                if !last.explicit_reps && last.size() > 0 && size < block_size {
+                    last.reps += (block_size - size) / last.size();
+                }
+            }
+        }
+        Ok(CustomFormat {
+            formats,
+            block_size,
+        })
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Format one block, data is shorter than the block at the end of the
+    /// input: units overlapping the end see zeros, the ones past it print
+    /// spaces instead of their conversions
+    pub fn write_block(&self, out: &mut Vec<u8>, offset: u64, data: &[u8]) {
+        for format in self.formats.iter() {
+            let mut position = 0;
+            for unit in format.units.iter().filter(|unit| !unit.is_end()) {
+                for _ in 0..unit.reps {
+                    let mut consumed = false;
+                    for piece in unit.pieces.iter() {
+                        let spec = match piece {
+                            Piece::Text(text) => {
+                                out.extend_from_slice(text);
+                                continue;
+                            }
+                            Piece::Convert(spec) => spec,
+                        };
+                        if position >= data.len() {
+                            out.resize(out.len() + spec.width, b' ');
+                        } else if let Conversion::Offset(radix) = spec.conversion {
+                            write_int(out, spec, radix, offset + position as u64, 8);
+                        } else {
+                            let mut bytes = [0u8; 8];
+                            let end = (position + spec.size).min(data.len());
+                            bytes[..end - position].copy_from_slice(&data[position..end]);
+                            write_value(out, spec, &bytes[..spec.size]);
+                        }
+                        if spec.conversion.consumes_input() {
+                            position += spec.size;
+                            consumed = true;
+                        }
+                    }
+                    if !consumed {
+                        position += unit.byte_count.unwrap_or(0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Print the %_A units with the offset after the last byte
+    pub fn write_end(&self, out: &mut Vec<u8>, offset: u64) {
+        let units = self
+            .formats
+            .iter()
+            .flat_map(|format| format.units.iter())
+            .filter(|unit| unit.is_end());
+        for unit in units {
+            for piece in unit.pieces.iter() {
+                match piece {
+                    Piece::Text(text) => out.extend_from_slice(text),
+                    Piece::Convert(spec) => match spec.conversion {
+                        Conversion::EndOffset(radix) => write_int(out, spec, radix, offset, 8),
+                        _ => out.resize(out.len() + spec.width, b' '),
+                    },
+                }
+            }
+        }
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, spec: &Spec, bytes: &[u8]) {
+    // hexdump reads multi byte values in host order
+    let mut value: u64 = 0;
+    for byte in bytes.iter().rev() {
+        value = (value << 8) | *byte as u64;
+    }
+    match spec.conversion {
+        Conversion::Int(kind) => write_int(out, spec, kind, value, spec.size),
+        Conversion::Float(kind) => {
+            let value = if spec.size == 4 {
+                f32::from_bits(value as u32) as f64
+            } else {
+                f64::from_bits(value)
+            };
+            write_float(out, spec, kind, value);
+        }
+        Conversion::Char => write_text(out, spec, &bytes[..1]),
+        Conversion::Printable => {
+            let byte = if is_printable(bytes[0]) {
+                bytes[0]
+            } else {
+                b'.'
+            };
+            write_text(out, spec, &[byte]);
+        }
+        Conversion::Escaped => write_text(out, spec, escaped(bytes[0]).as_bytes()),
+        Conversion::AsciiName => write_text(out, spec, ascii_name(bytes[0]).as_bytes()),
+        Conversion::Offset(_) | Conversion::EndOffset(_) => {}
+    }
+}
+
+fn escaped(byte: u8) -> String {
+    match byte {
+        0 => "\\0".to_string(),
+        0x07 => "\\a".to_string(),
+        0x08 => "\\b".to_string(),
+        0x0c => "\\f".to_string(),
+        b'\n' => "\\n".to_string(),
+        b'\r' => "\\r".to_string(),
+        b'\t' => "\\t".to_string(),
+        0x0b => "\\v".to_string(),
+        byte if is_printable(byte) => (byte as char).to_string(),
+        byte => format!("{:03o}", byte),
+    }
+}
+
+fn ascii_name(byte: u8) -> String {
+    match byte {
+        0..=0x1f => CONTROL_NAMES[byte as usize].to_string(),
+        0x7f => "del".to_string(),
+        byte if is_printable(byte) => (byte as char).to_string(),
+        byte => format!("{:02x}", byte),
+    }
+}
+
+/// Text conversions: the precision cuts, the width pads with spaces
+fn write_text(out: &mut Vec<u8>, spec: &Spec, text: &[u8]) {
+    let text = &text[..spec.precision.unwrap_or(text.len()).min(text.len())];
+    let pad = spec.width.saturating_sub(text.len());
+    if !spec.flags.left {
+        out.resize(out.len() + pad, b' ');
+    }
+    out.extend_from_slice(text);
+    if spec.flags.left {
+        out.resize(out.len() + pad, b' ');
+    }
+}
+
+/// Numbers padded to the width, with zeros after the sign and prefix when
+/// zero_pad is set
+fn write_number(
+    out: &mut Vec<u8>,
+    spec: &Spec,
+    sign: &str,
+    prefix: &str,
+    digits: &str,
+    zero_pad: bool,
+) {
+    let pad = spec
+        .width
+        .saturating_sub(sign.len() + prefix.len() + digits.len());
+    if spec.flags.left {
+        out.extend_from_slice(sign.as_bytes());
+        out.extend_from_slice(prefix.as_bytes());
+        out.extend_from_slice(digits.as_bytes());
+        out.resize(out.len() + pad, b' ');
+    } else if zero_pad && spec.flags.zero {
+        out.extend_from_slice(sign.as_bytes());
+        out.extend_from_slice(prefix.as_bytes());
+        out.resize(out.len() + pad, b'0');
+        out.extend_from_slice(digits.as_bytes());
+    } else {
+        out.resize(out.len() + pad, b' ');
+        out.extend_from_slice(sign.as_bytes());
+        out.extend_from_slice(prefix.as_bytes());
+        out.extend_from_slice(digits.as_bytes());
+    }
+}
+
+fn write_int(out: &mut Vec<u8>, spec: &Spec, kind: u8, value: u64, size: usize) {
+    let signed = matches!(kind, b'd' | b'i');
+    let (negative, magnitude) = if signed {
+        let shift = 64 - size as u32 * 8;
+        let value = ((value << shift) as i64) >> shift;
+        (value < 0, value.unsigned_abs())
+    } else {
+        (false, value)
+    };
+    let mut digits = match kind {
+        b'o' => format!("{:o}", magnitude),
+        b'x' => format!("{:x}", magnitude),
+        b'X' => format!("{:X}", magnitude),
+        _ => magnitude.to_string(),
+    };
+    if let Some(precision) = spec.precision {
+        if precision == 0 && magnitude == 0 {
+            digits.clear();
+        } else if digits.len() < precision {
+            digits.insert_str(0, &"0".repeat(precision - digits.len()));
+        }
+    }
+    let sign = match (negative, signed) {
+        (true, _) => "-",
+        (false, true) if spec.flags.plus => "+",
+        (false, true) if spec.flags.space => " ",
+        _ => "",
+    };
+    let prefix = match kind {
+        b'x' if spec.flags.alternate && magnitude != 0 => "0x",
+        b'X' if spec.flags.alternate && magnitude != 0 => "0X",
+        b'o' if spec.flags.alternate && !digits.starts_with('0') => "0",
+        _ => "",
+    };
+    write_number(out, spec, sign, prefix, &digits, spec.precision.is_none());
+}
+
+/// printf %e: at least two exponent digits with a sign
+fn exponent_notation(value: f64, precision: usize) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+}
+
+/// printf %g: the shorter of %f and %e for precision significant digits
+fn general_notation(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    let rounded = format!("{:.*e}", precision - 1, value);
+    let exponent: i32 = rounded
+        .split_once('e')
+        .and_then(|(_, exponent)| exponent.parse().ok())
+        .unwrap_or(0);
+    let text = if exponent < -4 || exponent >= precision as i32 {
+        exponent_notation(value, precision - 1)
+    } else {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+    };
+    if alternate {
+        return text;
+    }
+    let (mantissa, exponent) = match text.find('e') {
+        Some(idx) => text.split_at(idx),
+        None => (text.as_str(), ""),
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    format!("{}{}", mantissa, exponent)
+}
+
+fn write_float(out: &mut Vec<u8>, spec: &Spec, kind: u8, value: f64) {
+    let sign = if value.is_sign_negative() && !value.is_nan() {
+        "-"
+    } else if spec.flags.plus {
+        "+"
+    } else if spec.flags.space {
+        " "
+    } else {
+        ""
+    };
+    let value = value.abs();
+    let precision = spec.precision.unwrap_or(6);
+    let body = if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        "inf".to_string()
+    } else {
+        match kind {
+            b'f' => format!("{:.*}", precision, value),
+            b'e' | b'E' => exponent_notation(value, precision),
+            _ => general_notation(value, precision, spec.flags.alternate),
+        }
+    };
+    let body = if kind.is_ascii_uppercase() {
+        body.to_uppercase()
+    } else {
+        body
+    };
+    write_number(out, spec, sign, "", &body, value.is_finite());
+}
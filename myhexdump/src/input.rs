@@ -0,0 +1,655 @@
+use std::alloc::{self, Layout};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::{FileExt, FileTypeExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::{mem, ptr, slice};
+
+use crate::decompress::{self, Decoded};
+use crate::error::Error;
+
+/// Name used on the command line to refer to stdin
+pub const STDIN_NAME: &str = "-";
+
+/// Largest piece of input handed to the consumers at once
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+/// Size of the reads for inputs that are not mapped (pipes, stdin, /proc)
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Decompressed data is handed out in smaller pieces, each one tagged with
+/// the compressed offset it was decoded at
+const DECODED_CHUNK_SIZE: usize = 4096;
+
+/// _IOR(0x12, 114, u64), the size in bytes of a block device. The read
+/// direction bit moves on the architectures with a 3 bit direction field.
+#[cfg(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+))]
+const BLKGETSIZE64: libc::Ioctl = 0x40081272;
+#[cfg(not(any(
+    target_arch = "mips",
+    target_arch = "mips64",
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "sparc64"
+)))]
+const BLKGETSIZE64: libc::Ioctl = 0x80081272;
+
+/// statfs magic of the filesystems whose files report a size of 0 but have
+/// data: proc, sysfs, debugfs and tracefs
+const PSEUDO_FS_MAGICS: [i64; 4] = [0x9fa0, 0x62656572, 0x64626720, 0x74726163];
+
+/// Alignment of the buffer used for O_DIRECT reads
+const DIRECT_ALIGNMENT: usize = 4096;
+
+/// Size of a block device, its metadata says 0
+fn device_size(file: &fs::File) -> Option<u64> {
+    let mut size: u64 = 0;
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), BLKGETSIZE64, &mut size) };
+    (ret == 0).then_some(size)
+}
+
+/// Logical sector size of a block device, reads must be aligned to it with O_DIRECT
+fn sector_size(file: &fs::File) -> usize {
+    let mut size: libc::c_int = 0;
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), libc::BLKSSZGET, &mut size) };
+    if ret == 0 && size > 0 {
+        size as usize
+    } else {
+        512
+    }
+}
+
+/// Regular file of /proc or /sys, which report a size of 0
+fn is_pseudo_file(file: &fs::File) -> bool {
+    let mut stat: libc::statfs = unsafe { mem::zeroed() };
+    if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) } != 0 {
+        return false;
+    }
+    PSEUDO_FS_MAGICS.contains(&(stat.f_type as i64))
+}
+
+/// Real size of an opened input: regular files and block devices. None when
+/// it cannot be known up front (pipes, terminals, /proc and /sys files).
+pub fn file_size(file: &fs::File) -> Option<u64> {
+    let meta = file.metadata().ok()?;
+    let file_type = meta.file_type();
+    if file_type.is_block_device() {
+        device_size(file)
+    } else if meta.is_file() && !(meta.len() == 0 && is_pseudo_file(file)) {
+        Some(meta.len())
+    } else {
+        None
+    }
+}
+
+/// Size of the input called name, "-" being stdin
+pub fn path_size(name: &str) -> Option<u64> {
+    let path = if name == STDIN_NAME {
+        "/dev/stdin"
+    } else {
+        name
+    };
+    let file_type = fs::metadata(path).ok()?.file_type();
+    // opening a fifo waits for a writer, and its size is unknown anyway
+    if !file_type.is_file() && !file_type.is_block_device() {
+        return None;
+    }
+    file_size(&fs::File::open(path).ok()?)
+}
+
+/// Heap buffer aligned for O_DIRECT
+struct AlignedBuffer {
+    data: *mut u8,
+    layout: Layout,
+}
+
+impl AlignedBuffer {
+    fn new(len: usize, align: usize) -> AlignedBuffer {
+        let layout = Layout::from_size_align(len, align).expect("valid buffer layout");
+        let data = unsafe { alloc::alloc_zeroed(layout) };
+        if data.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        AlignedBuffer { data, layout }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.data, self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.data, self.layout) };
+    }
+}
+
+/// Block devices (and files opened with O_DIRECT) read with positioned
+/// reads whose offset and length are multiples of the block size, so
+/// skipping is free and O_DIRECT reads are accepted.
+struct AlignedReader {
+    file: fs::File,
+    size: u64,
+    position: u64,
+    block_size: u64,
+    buffer: AlignedBuffer,
+}
+
+impl AlignedReader {
+    fn new(file: fs::File, size: u64, block_size: usize) -> AlignedReader {
+        // the read length is a whole number of blocks
+        let len = READ_BUFFER_SIZE.next_multiple_of(block_size);
+        AlignedReader {
+            file,
+            size,
+            position: 0,
+            block_size: block_size as u64,
+            buffer: AlignedBuffer::new(len, block_size.max(DIRECT_ALIGNMENT)),
+        }
+    }
+
+    fn skip(&mut self, count: u64) -> u64 {
+        let step = count.min(self.size - self.position);
+        self.position += step;
+        step
+    }
+}
+
+impl Read for AlignedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let start = self.position - self.position % self.block_size;
+        let lead = (self.position - start) as usize;
+        let buffer = self.buffer.as_mut_slice();
+        let wanted = (lead + buf.len())
+            .next_multiple_of(self.block_size as usize)
+            .min(buffer.len());
+        let read = self.file.read_at(&mut buffer[..wanted], start)?;
+        let len = read
+            .saturating_sub(lead)
+            .min(buf.len())
+            .min((self.size - self.position) as usize);
+        buf[..len].copy_from_slice(&buffer[lead..lead + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+/// Read only mapping of a whole regular file, so large files are dumped
+/// without copying them through a read buffer
+struct Mapping {
+    data: *mut libc::c_void,
+    len: usize,
+    position: usize,
+}
+
+impl Mapping {
+    fn new(file: &fs::File, len: u64) -> Option<Mapping> {
+        let len = usize::try_from(len).ok()?;
+        let data = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if data == libc::MAP_FAILED {
+            return None;
+        }
+        // only a hint for the read ahead, failing is harmless
+        unsafe { libc::madvise(data, len, libc::MADV_SEQUENTIAL) };
+        Some(Mapping {
+            data,
+            len,
+            position: 0,
+        })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.data as *const u8, self.len) }
+    }
+
+    fn remaining(&self) -> usize {
+        self.len - self.position
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.data, self.len) };
+    }
+}
+
+enum Source {
+    File(fs::File),
+    Mapped(Mapping),
+    Aligned(AlignedReader),
+    Stdin(io::Stdin),
+    /// the decompressed data of the input, or the input as is when it is not
+    /// compressed
+    Decoded(Decoded),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(file) => file.read(buf),
+            Source::Mapped(map) => {
+                let len = map.remaining().min(buf.len());
+                buf[..len].copy_from_slice(&map.as_slice()[map.position..map.position + len]);
+                map.position += len;
+                Ok(len)
+            }
+            Source::Aligned(reader) => reader.read(buf),
+            Source::Stdin(stdin) => stdin.read(buf),
+            Source::Decoded(decoded) => decoded.reader.read(buf),
+        }
+    }
+}
+
+/// Data that can be skipped into and read in pieces, what dumps are made of
+pub trait Input {
+    /// Skip count bytes, returns the number of bytes actually skipped, which
+    /// is less than count only when the end of the data was reached
+    fn skip(&mut self, count: u64) -> io::Result<u64>;
+
+    /// Next piece of the data, at most max bytes and empty at the end
+    fn next_chunk(&mut self, max: usize) -> io::Result<&[u8]>;
+
+    /// Next piece of the data and the offset in the underlying file it was
+    /// decoded at, for data decoded from another format
+    fn next_chunk_with_source(&mut self, max: usize) -> io::Result<(&[u8], Option<u64>)> {
+        Ok((self.next_chunk(max)?, None))
+    }
+}
+
+/// All the inputs given on the command line read back to back as a single
+/// stream, so offsets keep counting across files like hexdump does.
+pub struct InputStream {
+    names: Vec<String>,
+    next: usize,
+    current: Option<Source>,
+    had_error: bool,
+    buffer: Vec<u8>,
+    /// open files and block devices with O_DIRECT, bypassing the page cache
+    direct: bool,
+    /// map regular files instead of reading them
+    mmap: bool,
+    /// decompress the inputs that are gzip, zstd, xz or bzip2
+    decompress: bool,
+    /// compressed offset of the data of the last read
+    source_offset: Option<u64>,
+}
+
+impl InputStream {
+    pub fn new(names: Vec<String>) -> InputStream {
+        InputStream {
+            names,
+            next: 0,
+            current: None,
+            had_error: false,
+            buffer: vec![0; READ_BUFFER_SIZE],
+            direct: false,
+            mmap: false,
+            decompress: false,
+            source_offset: None,
+        }
+    }
+
+    /// Read files and block devices with O_DIRECT instead of through the
+    /// page cache
+    pub fn with_direct_io(mut self, direct: bool) -> InputStream {
+        self.direct = direct;
+        self
+    }
+
+    /// Map regular files into memory instead of reading them. A file
+    /// truncated by another process while it is mapped raises SIGBUS on the
+    /// pages past its new end and kills the dump, which reading survives, so
+    /// this is only done when asked for.
+    pub fn with_mmap(mut self, mmap: bool) -> InputStream {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Read the decompressed data of the inputs that are compressed with
+    /// gzip, zstd, xz or bzip2, detected by their magic number
+    pub fn with_decompression(mut self, decompress: bool) -> InputStream {
+        self.decompress = decompress;
+        self
+    }
+
+    /// True if any of the inputs could not be opened or read
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+
+    /// Total size of all the inputs, None when the size of one of them is
+    /// unknown (a pipe, a terminal, a /proc file, a compressed file...)
+    pub fn total_size(&self) -> Option<u64> {
+        if self.decompress {
+            return None;
+        }
+        let mut total: u64 = 0;
+        for name in self.names.iter() {
+            total += path_size(name)?;
+        }
+        Some(total)
+    }
+
+    /// Make sure there is an open input, moving on to the next one if needed.
+    /// Returns false once all the inputs were consumed.
+    fn open_next(&mut self) -> bool {
+        while self.current.is_none() {
+            let name = match self.names.get(self.next) {
+                Some(name) => name.to_owned(),
+                None => return false,
+            };
+            self.next += 1;
+
+            let source = if self.decompress {
+                open_decoded(&name)
+            } else if name == STDIN_NAME {
+                Ok(Source::Stdin(io::stdin()))
+            } else {
+                open_source(&name, self.direct, self.mmap)
+            };
+            match source {
+                Ok(source) => self.current = Some(source),
+                Err(e) => {
+                    eprintln!("ERROR: Failed to open file [{}], reason: {}", name, e);
+                    self.had_error = true;
+                }
+            }
+        }
+        true
+    }
+
+    /// Read into the buffer, with the compressed offset of the data
+    fn read_chunk(&mut self, max: usize) -> (&[u8], Option<u64>) {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        let wanted = if self.decompress {
+            max.min(DECODED_CHUNK_SIZE)
+        } else {
+            max.min(buffer.len())
+        };
+        // input errors are reported by read, which moves on to the next file
+        let len = self.read(&mut buffer[..wanted]).unwrap_or_default();
+        self.buffer = buffer;
+        (&self.buffer[..len], self.source_offset)
+    }
+}
+
+/// Errors opening or reading one of the inputs are reported and the stream
+/// moves on to the next input, like hexdump. They never fail the dump, check
+/// had_error at the end instead.
+impl Input for InputStream {
+    /// Regular files are seeked over, anything else (pipes, stdin, files
+    /// reporting size 0 like /proc) is read and discarded
+    fn skip(&mut self, count: u64) -> io::Result<u64> {
+        let mut skipped: u64 = 0;
+        while skipped < count && self.open_next() {
+            let wanted = count - skipped;
+            let done = match self.current.as_mut() {
+                Some(Source::File(file)) => match skip_file(file, wanted) {
+                    Ok(done) => done,
+                    Err(e) => {
+                        eprintln!("ERROR: Failed to skip into file, reason: {}", e);
+                        self.had_error = true;
+                        0
+                    }
+                },
+                Some(Source::Mapped(map)) => {
+                    let step = (map.remaining() as u64).min(wanted);
+                    map.position += step as usize;
+                    step
+                }
+                Some(Source::Aligned(reader)) => reader.skip(wanted),
+                Some(Source::Stdin(stdin)) => discard(stdin, wanted),
+                Some(Source::Decoded(decoded)) => discard(&mut decoded.reader, wanted),
+                None => 0,
+            };
+            skipped += done;
+            if done < wanted {
+                // this input is exhausted, continue with the next one
+                self.current = None;
+            }
+        }
+        Ok(skipped)
+    }
+
+    fn next_chunk(&mut self, max: usize) -> io::Result<&[u8]> {
+        Ok(self.next_chunk_with_source(max)?.0)
+    }
+
+    /// Mapped files are handed out without a copy, decompressed data comes
+    /// with its compressed offset
+    fn next_chunk_with_source(&mut self, max: usize) -> io::Result<(&[u8], Option<u64>)> {
+        let mut range = None;
+        while range.is_none() && self.open_next() {
+            match self.current.as_mut() {
+                Some(Source::Mapped(map)) => {
+                    let len = map.remaining().min(max);
+                    if len == 0 {
+                        self.current = None;
+                    } else {
+                        range = Some(map.position..map.position + len);
+                        map.position += len;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let range = match range {
+            Some(range) => range,
+            None => return Ok(self.read_chunk(max)),
+        };
+        match &self.current {
+            Some(Source::Mapped(map)) => Ok((&map.as_slice()[range], None)),
+            _ => Ok((&[], None)),
+        }
+    }
+}
+
+/// Decompressed data of a compressed file or stdin, the data as is otherwise.
+/// Block devices are read as a stream here, without the aligned reads of
+/// open_source, so clap rejects --direct together with --decompress.
+fn open_decoded(name: &str) -> io::Result<Source> {
+    let reader: Box<dyn Read> = if name == STDIN_NAME {
+        Box::new(io::stdin())
+    } else {
+        Box::new(fs::File::open(name)?)
+    };
+    Ok(Source::Decoded(decompress::decode(reader)?))
+}
+
+/// Block devices and O_DIRECT files get aligned reads, regular files are
+/// mapped when mmap is set, anything else (or a failed mapping) is read as a
+/// stream
+fn open_source(name: &str, direct: bool, mmap: bool) -> io::Result<Source> {
+    let file = fs::File::open(name)?;
+    let meta = file.metadata()?;
+    let block_device = meta.file_type().is_block_device();
+    let aligned = block_device || (direct && meta.is_file());
+    if !aligned {
+        // files reporting size 0 (like in /proc) still have data to read.
+        // Growing files, like logs still being written, are fine: the
+        // mapping just stops at the size seen here.
+        if mmap && meta.is_file() && meta.len() > 0 {
+            if let Some(map) = Mapping::new(&file, meta.len()) {
+                return Ok(Source::Mapped(map));
+            }
+        }
+        return Ok(Source::File(file));
+    }
+
+    let size = match file_size(&file) {
+        Some(size) => size,
+        None => return Ok(Source::File(file)),
+    };
+    let block_size = if block_device {
+        sector_size(&file)
+    } else {
+        meta.blksize() as usize
+    };
+    let file = if direct {
+        fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(name)?
+    } else {
+        file
+    };
+    Ok(Source::Aligned(AlignedReader::new(file, size, block_size)))
+}
+
+fn skip_file(file: &mut fs::File, count: u64) -> io::Result<u64> {
+    let meta = file.metadata()?;
+    // character devices, fifos and /proc files can only be read forward
+    if !meta.is_file() || meta.len() == 0 {
+        return Ok(discard(file, count));
+    }
+    let position = file.stream_position()?;
+    let available = meta.len().saturating_sub(position);
+    let step = count.min(available);
+    file.seek(SeekFrom::Current(step as i64))?;
+    Ok(step)
+}
+
+fn discard<R: Read>(reader: &mut R, count: u64) -> u64 {
+    io::copy(&mut reader.by_ref().take(count), &mut io::sink()).unwrap_or(0)
+}
+
+impl Read for InputStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.open_next() {
+            self.source_offset = match &self.current {
+                Some(Source::Decoded(decoded)) => {
+                    decoded.position.as_ref().map(|position| position.get())
+                }
+                _ => None,
+            };
+            let result = match self.current.as_mut() {
+                Some(source) => source.read(buf),
+                None => Ok(0),
+            };
+            match result {
+                Ok(0) => self.current = None,
+                Ok(n) => return Ok(n),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let name = &self.names[self.next - 1];
+                    eprintln!("ERROR: Failed to read from file [{}] ({})", name, e);
+                    self.had_error = true;
+                    self.current = None;
+                }
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// Input reading from any reader, for data that is not in files (a slice,
+/// a socket, a decoder...)
+pub struct ReadInput<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> ReadInput<R> {
+    pub fn new(reader: R) -> ReadInput<R> {
+        ReadInput {
+            reader,
+            buffer: vec![0; READ_BUFFER_SIZE],
+        }
+    }
+}
+
+impl<R: Read> Input for ReadInput<R> {
+    fn skip(&mut self, count: u64) -> io::Result<u64> {
+        io::copy(&mut self.reader.by_ref().take(count), &mut io::sink())
+    }
+
+    fn next_chunk(&mut self, max: usize) -> io::Result<&[u8]> {
+        let wanted = max.min(self.buffer.len());
+        loop {
+            match self.reader.read(&mut self.buffer[..wanted]) {
+                Ok(len) => return Ok(&self.buffer[..len]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Skip to offset and hand at most length bytes to consume as they are read,
+/// stopping at the first error
+pub fn stream<I, F>(
+    input: &mut I,
+    offset: u64,
+    length: Option<u64>,
+    mut consume: F,
+) -> Result<(), Error>
+where
+    I: Input + ?Sized,
+    F: FnMut(&[u8]) -> Result<(), Error>,
+{
+    stream_with_source(input, offset, length, |data, _| consume(data))
+}
+
+/// Same as stream, also handing the source offset of every piece to consume
+pub fn stream_with_source<I, F>(
+    input: &mut I,
+    offset: u64,
+    length: Option<u64>,
+    mut consume: F,
+) -> Result<(), Error>
+where
+    I: Input + ?Sized,
+    F: FnMut(&[u8], Option<u64>) -> Result<(), Error>,
+{
+    if offset != 0 {
+        let skipped = input.skip(offset).map_err(Error::Input)?;
+        if skipped < offset {
+            return Err(Error::SkipPastEnd {
+                offset,
+                size: skipped,
+            });
+        }
+    }
+
+    let mut remaining: Option<u64> = length;
+
+    loop {
+        // never read past -n, so stdin is left untouched after it
+        let wanted = match remaining {
+            Some(0) => break,
+            Some(remaining) => remaining.min(CHUNK_SIZE as u64) as usize,
+            None => CHUNK_SIZE,
+        };
+        let (chunk, source_offset) = input.next_chunk_with_source(wanted).map_err(Error::Input)?;
+        if chunk.is_empty() {
+            // reached the end of all the inputs
+            break;
+        }
+
+        // reads can be short (pipes, end of one of the files), consumers
+        // keep partial rows until the rest arrives
+        consume(chunk, source_offset)?;
+        if let Some(remaining) = remaining.as_mut() {
+            *remaining -= chunk.len() as u64;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Hex dumps in the hexdump, hexdump -C and xxd layouts, and the tools of the
+//! myhexdump binary built on them: diff, search, strings, templates, patching
+//! and statistics.
+//!
+//! A [`Dumper`] writes the dump of an [`Input`] to any `io::Write`:
+//!
+//! ```
+//! use myhexdump::{Dumper, Layout, ReadInput, RowFormat};
+//!
+//! let mut out = Vec::new();
+//! let mut input = ReadInput::new(&b"Hello, hex\n"[..]);
+//! Dumper::new(&mut out, RowFormat::new(Layout::Canonical))
+//!     .with_offset(7)
+//!     .dump(&mut input)
+//!     .unwrap();
+//! assert_eq!(
+//!     String::from_utf8(out).unwrap(),
+//!     "00000007  68 65 78 0a                                       |hex.|\n0000000b\n"
+//! );
+//! ```
+
+pub mod color;
+pub mod decompress;
+pub mod diff;
+pub mod dump;
+pub mod emit;
+pub mod error;
+pub mod format;
+pub mod input;
+pub mod patch;
+pub mod revert;
+pub mod search;
+pub mod size;
+pub mod stats;
+pub mod strings;
+pub mod template;
+mod terminal;
+pub mod viewer;
+
+pub use dump::Dumper;
+pub use error::Error;
+pub use format::{Layout, RowFormat};
+pub use input::{Input, InputStream, ReadInput};
@@ -1,148 +1,532 @@
 use std::fs;
-use std::io::Read;
-use std::io::Seek;
-use std::os::unix::fs::MetadataExt;
+use std::io::{self, BufReader, Read, Write};
 use std::process::exit;
 
-use clap::Parser;
+use clap::{ArgGroup, Parser, Subcommand};
 
-const BUFFER_SIZE: usize = 4096;
+use myhexdump::color::ColorMode;
+use myhexdump::diff::{self, DiffOptions, DIFF_ROW_SIZE};
+use myhexdump::emit::{self, Language};
+use myhexdump::format::{self, CustomFormat, Decode, Endian, Format, Layout, RowFormat, Words};
+use myhexdump::input::{self, InputStream, STDIN_NAME};
+use myhexdump::patch::{self, PatchOptions};
+use myhexdump::revert::Reverter;
+use myhexdump::search::{self, Pattern, Searcher};
+use myhexdump::size::{self, Offset};
+use myhexdump::stats::{self, Stats};
+use myhexdump::strings::StringsExtractor;
+use myhexdump::template::{self, Template};
+use myhexdump::{viewer, Dumper, Error};
 
 /// Simple HexDump Utility
 #[derive(Parser)]
 #[command(version)]
 struct Args {
-    /// File To Parse
-    filename: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// interpret only length bytes of input
-    #[arg(short = 'n', long, default_value = "0")]
+    /// Files To Parse, read stdin when none or - is given
+    filenames: Vec<String>,
+
+    /// interpret only length bytes of input (suffixes like 1K, 0x100, 2MiB are accepted)
+    #[arg(short = 'n', long, value_parser = size::parse_size)]
     length: Option<u64>,
 
-    /// offset bytes from the beginning
-    #[arg(short = 's', long = "skip", default_value = "0")]
-    offset: Option<u64>,
+    /// offset bytes from the beginning, negative values count from the end of input
+    #[arg(short = 's', long = "skip", value_parser = size::parse_offset, allow_hyphen_values = true)]
+    offset: Option<Offset>,
+
+    /// canonical hex+ASCII display, same as hexdump -C
+    #[arg(short = 'C', long, group = "layout", global = true)]
+    canonical: bool,
+
+    /// two-byte hexadecimal display, same as hexdump -x
+    #[arg(short = 'x', long = "two-bytes-hex", group = "layout", global = true)]
+    two_bytes_hex: bool,
+
+    /// two-byte octal display, same as hexdump -o
+    #[arg(short = 'o', long = "two-bytes-octal", group = "layout", global = true)]
+    two_bytes_octal: bool,
+
+    /// two-byte decimal display, same as hexdump -d
+    #[arg(
+        short = 'd',
+        long = "two-bytes-decimal",
+        group = "layout",
+        global = true
+    )]
+    two_bytes_decimal: bool,
+
+    /// xxd compatible output
+    #[arg(long, group = "layout", global = true)]
+    xxd: bool,
+
+    /// plain hex dump without offsets, same as xxd -p
+    #[arg(short = 'p', long, group = "layout", global = true)]
+    plain: bool,
+
+    /// hexdump format string, several can be given: [iterations][/byte_count] "format" units using
+    /// printf conversions (%d %o %u %x %c %e %f %g), %_a/%_A offsets and %_p/%_c/%_u characters
+    #[arg(short = 'e', long = "format", value_name = "FORMAT", value_parser = format::parse_format,
+          allow_hyphen_values = true, conflicts_with_all = ["layout", "group", "template", "diff", "find", "strings", "interactive"])]
+    formats: Vec<Format>,
+
+    /// print the -e formats equivalent to the selected layout, as a starting point for custom ones
+    #[arg(long, conflicts_with = "formats")]
+    show_format: bool,
+
+    /// xxd/plain/diff: octets per line
+    #[arg(short = 'c', long, global = true)]
+    cols: Option<usize>,
+
+    /// xxd: octets per group, 0 for no grouping
+    #[arg(short = 'g', long, requires = "xxd", global = true)]
+    groupsize: Option<usize>,
+
+    /// default and -C layouts: show the hex pane as words of 1, 2, 4 or 8 bytes
+    #[arg(long, value_name = "SIZE", value_parser = format::parse_group, global = true,
+          conflicts_with_all = ["two_bytes_hex", "two_bytes_octal", "two_bytes_decimal", "xxd", "plain"])]
+    group: Option<usize>,
+
+    /// group: byte order of the words
+    #[arg(long, value_enum, default_value_t = Endian::Little, requires = "group", global = true)]
+    endian: Endian,
+
+    /// group: print the value of every word after the ascii pane, floats need words of 4 or 8 bytes
+    #[arg(long, value_enum, requires = "group", global = true)]
+    decode: Option<Decode>,
+
+    /// convert a dump (ours, hexdump -C or xxd) back to binary, with -p read plain hex
+    #[arg(short = 'r', long, conflicts_with_all = ["length", "offset"])]
+    revert: bool,
+
+    /// display all input data, do not collapse identical rows into '*'
+    #[arg(short = 'v', long = "no-squeezing")]
+    no_squeezing: bool,
+
+    /// color bytes by class: NUL, printable, whitespace, control and high bytes
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto, global = true)]
+    color: ColorMode,
+
+    /// annotate the dump with the fields of a format: elf, png, zip, gzip or a custom .toml template
+    #[arg(long, value_parser = template::parse_template)]
+    template: Option<Template>,
+
+    /// dump two files side by side and highlight the bytes that differ
+    #[arg(long, num_args = 2, value_names = ["A", "B"], conflicts_with_all = ["filenames", "revert", "layout", "group"])]
+    diff: Option<Vec<String>>,
+
+    /// diff: print only the rows that differ and the context rows around them
+    #[arg(long, requires = "diff")]
+    only_diff: bool,
+
+    /// print the offset of every match of a hex pattern ("de ad ?? ef", ?? matches any byte) or a quoted "text"
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["diff", "revert", "template"])]
+    find: Option<String>,
+
+    /// print the printable ASCII and UTF-16LE strings of at least MIN (default 4) chars with their offset, like strings -t x
+    #[arg(long, value_name = "MIN", num_args = 0..=1, require_equals = true,
+          default_missing_value = "4", conflicts_with_all = ["diff", "revert", "template", "find"])]
+    strings: Option<usize>,
+
+    /// browse a file in a full-screen pager with a cursor, search and bookmarks
+    #[arg(short = 'i', long, conflicts_with_all = ["diff", "revert", "template", "find", "strings", "length"])]
+    interactive: bool,
+
+    /// print a byte histogram, the entropy and an entropy graph per block to spot compressed or encrypted regions
+    #[arg(long, conflicts_with_all = ["diff", "revert", "template", "find", "strings", "interactive", "formats"])]
+    stats: bool,
+
+    /// print the selected bytes as a C, Rust or Python array or as base64, to embed them in source code like xxd -i
+    #[arg(long, value_name = "LANGUAGE", conflicts_with_all = ["diff", "revert", "template", "find", "strings", "interactive", "formats", "stats"])]
+    emit: Option<Language>,
+
+    /// emit: name of the variable (derived from the file name by default)
+    #[arg(long, value_name = "NAME", value_parser = emit::parse_name, requires = "emit")]
+    name: Option<String>,
+
+    /// stats: bytes per block of the entropy graph (picked for about 64 rows by default)
+    #[arg(long, value_name = "SIZE", value_parser = size::parse_size, requires = "stats")]
+    block_size: Option<u64>,
+
+    /// find: search text as UTF-16LE instead of UTF-8
+    #[arg(long, requires = "find")]
+    utf16: bool,
+
+    /// rows of context printed around differences (default 1) or matches (default 0)
+    #[arg(long)]
+    context: Option<usize>,
+
+    /// read files and block devices with O_DIRECT, bypassing the page cache
+    #[arg(long)]
+    direct: bool,
+
+    /// map regular files into memory instead of reading them, faster on large files, but a file
+    /// truncated by another process during the dump kills it with SIGBUS
+    #[arg(long, conflicts_with_all = ["direct", "decompress"])]
+    mmap: bool,
+
+    /// dump the decompressed data of gzip, zstd, xz and bzip2 inputs (detected by their magic
+    /// number, other inputs are dumped as is), offsets count decompressed bytes
+    #[arg(short = 'z', long, conflicts_with_all = ["diff", "revert", "interactive", "direct"])]
+    decompress: bool,
+
+    /// decompress: start every row with the [offset] in the compressed input it was decoded at
+    #[arg(long, requires = "decompress", conflicts_with_all = ["template", "find", "strings", "stats", "emit"])]
+    compressed_offsets: bool,
+
+    /// revert: write the binary to this file instead of stdout, gaps become sparse regions
+    #[arg(long, requires = "revert")]
+    output: Option<String>,
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(Subcommand)]
+enum Command {
+    /// overwrite bytes of a file in place
+    Patch(PatchArgs),
+}
 
-    let filename = args.filename;
+#[derive(clap::Args)]
+#[command(group(ArgGroup::new("data").required(true).args(["bytes", "from_file"])))]
+struct PatchArgs {
+    /// file to patch
+    file: String,
 
-    let length: u64 = args.length.unwrap_or_default();
-    let offset: u64 = args.offset.unwrap_or_default();
+    /// offset of the first patched byte, negative values count from the end of the file
+    #[arg(long, value_parser = size::parse_offset, allow_hyphen_values = true)]
+    at: Offset,
 
-    println!("HexDump File    [{}]", filename);
-    println!("length:         [{}]", length);
-    println!("offset:         [{}]", offset);
+    /// bytes to write: hex ("90 90", "deadbeef") where ?? keeps the original byte, or a quoted "text"
+    #[arg(long)]
+    bytes: Option<String>,
 
-    let mut file = match fs::File::open(&filename) {
-        Ok(f) => f,
-        Err(e) => {
-            eprintln!("ERROR: Failed to open file [{}], reason: {}", &filename, e);
-            exit(1);
-        }
-    };
+    /// write the content of this file
+    #[arg(long)]
+    from_file: Option<String>,
 
-    let meta = match file.metadata() {
-        Ok(meta) => meta,
-        Err(e) => {
-            eprintln!(
-                "ERROR: Failed to read metadata for file [{}], reason: {}",
-                &filename, e
-            );
-            exit(1);
+    /// print the rows before and after the patch without touching the file
+    #[arg(long)]
+    dry_run: bool,
+
+    /// copy the file to FILE.bak before patching
+    #[arg(long)]
+    backup: bool,
+
+    /// allow patches that run past the end of the file and grow it
+    #[arg(long)]
+    allow_extend: bool,
+}
+
+impl Args {
+    fn layout(&self) -> Layout {
+        if self.canonical {
+            Layout::Canonical
+        } else if self.two_bytes_hex {
+            Layout::Hex16
+        } else if self.two_bytes_octal {
+            Layout::Octal16
+        } else if self.two_bytes_decimal {
+            Layout::Decimal16
+        } else if self.xxd {
+            Layout::Xxd
+        } else if self.plain {
+            Layout::Plain
+        } else {
+            Layout::Default
         }
-    };
+    }
+}
 
-    let size = meta.size();
-    println!("File Size:      [{}] bytes", size);
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        if !e.is_silent() {
+            eprintln!("ERROR: {}", e);
+        }
+        exit(e.exit_code());
+    }
+}
 
-    if offset != 0 {
-        if offset > size {
-            eprintln!(
-                "ERROR: Skipped[{}] past the end of file size[{}]",
-                offset, size
-            );
-            exit(1);
+fn run(args: Args) -> Result<(), Error> {
+    if args.show_format {
+        let formats = args.layout().format_strings();
+        if formats.is_empty() {
+            return Err(Error::Message(
+                "the xxd layouts have no hexdump format string".to_string(),
+            ));
         }
-        match file.seek(std::io::SeekFrom::Start(offset)) {
-            Ok(x) => x,
-            Err(e) => {
-                eprintln!(
-                    "ERROR: Failed to skip [{}] bytes into file, reason: {}",
-                    offset, e
-                );
-                exit(1);
-            }
-        };
+        let mut out = io::stdout().lock();
+        for format in formats {
+            writeln!(out, "-e '{}'", format).map_err(Error::Output)?;
+        }
+        return Ok(());
+    }
+
+    let words = match args.group {
+        Some(size) => Some(Words::new(size, args.endian, args.decode).map_err(Error::Message)?),
+        None => None,
+    };
+    let row_format = if args.formats.is_empty() {
+        RowFormat::new(args.layout())
+            .with_xxd_options(args.cols, args.groupsize)
+            .with_words(words)
+            .with_color(args.color.enabled())
+    } else {
+        RowFormat::custom(CustomFormat::new(args.formats.clone()).map_err(Error::Message)?)
+    };
+
+    if let Some(Command::Patch(patch)) = &args.command {
+        return run_patch(patch, &row_format);
     }
 
-    let mut buffer: Vec<u8> = vec![0; BUFFER_SIZE];
+    let mut filenames = args.filenames;
+    if filenames.is_empty() {
+        filenames.push(STDIN_NAME.to_string());
+    }
 
-    let mut bytes_remaining = size - offset;
-    if length != 0 && length != bytes_remaining {
-        bytes_remaining = length;
+    if args.revert {
+        let mut reverter =
+            Reverter::new(args.output.as_deref(), args.plain).map_err(Error::Message)?;
+        let mut input = InputStream::new(filenames);
+        reverter
+            .run(BufReader::new(&mut input))
+            .map_err(|e| Error::Message(format!("Failed to revert dump, {}", e)))?;
+        return inputs_result(&input);
     }
-    let mut printed_offset: u64 = offset;
-    let mut bytes_printed: u64 = 0;
 
-    while bytes_remaining > 0 {
-        let bytes_read = match file.read(&mut buffer[..]) {
-            Ok(n) => n,
-            Err(e) => {
-                eprintln!("ERROR: Failed to read from file ({})", e);
-                exit(1);
-            }
+    if let Some(names) = args.diff {
+        let options = DiffOptions {
+            row_size: args.cols.unwrap_or(DIFF_ROW_SIZE).max(1),
+            only_diff: args.only_diff,
+            context: args.context.unwrap_or(1),
+            color: args.color.enabled(),
         };
-        let mut col_count: u8 = 0;
-        let mut new_line: bool = true;
-        let mut ascii_out: String = String::new();
-        for item in buffer.iter().take(bytes_read) {
-            if new_line {
-                print!("{:08x} | ", printed_offset);
-                new_line = false;
-            }
-            print!("{:02x} ", item);
-            if *item >= 32 && *item <= 126 {
-                ascii_out.push(*item as char);
-            } else {
-                ascii_out.push('.');
-            }
-            bytes_printed += 1;
-            col_count += 1;
-            if col_count == 8 {
-                print!(" ");
-            }
-            if col_count >= 16 {
-                println!(" |{ascii_out}|");
-                new_line = true;
-                col_count = 0;
-                printed_offset += 16;
-                ascii_out.clear();
-            }
-            if bytes_printed >= length {
-                // reached n bytes to dump
-                break;
+        // negative offsets count from the end of the first file
+        let offset = match input::path_size(&names[0]) {
+            Some(size) => args.offset.unwrap_or(Offset::FromStart(0)).resolve(size),
+            None => 0,
+        };
+        // same exit codes as cmp: 0 identical, 1 different, 2 trouble
+        let summary = diff::diff_files(
+            &mut io::stdout().lock(),
+            &names[0],
+            &names[1],
+            offset,
+            args.length,
+            &options,
+        )
+        .map_err(|e| Error::Trouble(Box::new(e)))?;
+        return match summary.first_difference {
+            Some(_) => Err(Error::Differ),
+            None => Ok(()),
+        };
+    }
+
+    if args.interactive {
+        if filenames.len() != 1 || filenames[0] == STDIN_NAME {
+            return Err(Error::Message(
+                "--interactive needs exactly one file".to_string(),
+            ));
+        }
+        let start = args.offset.unwrap_or(Offset::FromStart(0));
+        return viewer::run(&filenames[0], start).map_err(Error::Message);
+    }
+
+    let mut input = InputStream::new(filenames.clone())
+        .with_direct_io(args.direct)
+        .with_mmap(args.mmap)
+        .with_decompression(args.decompress);
+
+    let start = args.offset.unwrap_or(Offset::FromStart(0));
+    let offset: u64 = match start {
+        Offset::FromStart(offset) => offset,
+        Offset::FromEnd(_) => {
+            match input.total_size() {
+                Some(total) => start.resolve(total),
+                None if args.decompress => {
+                    return Err(Error::Message("A negative offset needs the decompressed size, which is not known with --decompress".to_string()));
+                }
+                None => {
+                    return Err(Error::Message("A negative offset needs inputs of known size (regular files or block devices)".to_string()));
+                }
             }
         }
-        if col_count != 0 {
-            // we ended with a partial row, print out the ascii text
-            let padding = (16 - col_count) * 3;
-            let pad_str = " ".to_string().repeat(padding.into());
-            print!("{}", pad_str);
-            if col_count < 8 {
-                // add extra space to account for extra space in the middle of the line
-                print!(" ");
+    };
+
+    if let Some(pattern) = &args.find {
+        // same exit codes as grep: 0 found, 1 not found, 2 trouble
+        let trouble = |e| Error::Trouble(Box::new(e));
+        let pattern: Pattern =
+            search::parse_pattern(pattern, args.utf16).map_err(|e| trouble(Error::Message(e)))?;
+        let mut searcher = Searcher::new(pattern, row_format, args.context.unwrap_or(0), offset);
+        let mut out = io::stdout().lock();
+        input::stream(&mut input, offset, args.length, |data| {
+            searcher.feed(&mut out, data).map_err(Error::Output)
+        })
+        .map_err(trouble)?;
+        let matches = searcher
+            .finish(&mut out)
+            .map_err(|e| trouble(Error::Output(e)))?;
+        inputs_result(&input).map_err(trouble)?;
+        return match matches {
+            0 => Err(Error::NotFound),
+            _ => Ok(()),
+        };
+    }
+
+    if let Some(min_length) = args.strings {
+        let mut extractor = StringsExtractor::new(min_length, offset);
+        let mut out = io::stdout().lock();
+        input::stream(&mut input, offset, args.length, |data| {
+            extractor.feed(&mut out, data).map_err(Error::Output)
+        })?;
+        extractor.finish(&mut out).map_err(Error::Output)?;
+        return inputs_result(&input);
+    }
+
+    if args.stats {
+        let block_size = args.block_size.unwrap_or_else(|| {
+            let available = input.total_size().map(|total| total.saturating_sub(offset));
+            match (args.length, available) {
+                (Some(length), Some(available)) => stats::block_size_for(length.min(available)),
+                (Some(length), None) => stats::block_size_for(length),
+                (None, Some(available)) => stats::block_size_for(available),
+                (None, None) => stats::DEFAULT_BLOCK_SIZE,
             }
+        });
+        let mut stats = Stats::new(block_size, offset);
+        input::stream(&mut input, offset, args.length, |data| {
+            stats.feed(data);
+            Ok(())
+        })?;
+        stats
+            .finish(&mut io::stdout().lock())
+            .map_err(Error::Output)?;
+        return inputs_result(&input);
+    }
 
-            println!(" |{ascii_out}|");
+    if let Some(language) = args.emit {
+        // the arrays start with their length, so the whole range is needed first
+        let mut data = Vec::new();
+        input::stream(&mut input, offset, args.length, |chunk| {
+            data.extend_from_slice(chunk);
+            Ok(())
+        })?;
+        if data.is_empty() && language == Language::C {
+            return Err(Error::Message(
+                "Nothing to emit, C does not allow empty arrays".to_string(),
+            ));
         }
+        let name = args.name.unwrap_or_else(|| match filenames.as_slice() {
+            [filename] if filename != STDIN_NAME => emit::identifier(filename),
+            _ => "data".to_string(),
+        });
+        emit::emit(&mut io::stdout().lock(), language, &name, &data).map_err(Error::Output)?;
+        return inputs_result(&input);
+    }
 
-        if bytes_remaining > bytes_read as u64 {
-            bytes_remaining -= bytes_read as u64;
-        } else {
-            bytes_remaining = 0;
+    let mut out = io::stdout().lock();
+    // the compatible layouts must match the original tools output exactly
+    if row_format.layout() == Layout::Default {
+        write_header(&mut out, &filenames, offset, args.length).map_err(Error::Output)?;
+    }
+
+    let mut dumper = Dumper::new(out, row_format)
+        .with_offset(offset)
+        .with_length(args.length);
+    if args.no_squeezing {
+        dumper = dumper.no_squeeze();
+    }
+    if args.compressed_offsets {
+        dumper = dumper.with_source_offsets();
+    }
+
+    match &args.template {
+        Some(template) => dump_with_template(&mut input, dumper, template, offset, args.length)?,
+        None => dumper.dump(&mut input)?,
+    }
+    inputs_result(&input)
+}
+
+/// Header of the default layout, naming the files and the dumped range
+fn write_header<W: Write>(
+    out: &mut W,
+    filenames: &[String],
+    offset: u64,
+    length: Option<u64>,
+) -> io::Result<()> {
+    for filename in filenames.iter() {
+        writeln!(out, "HexDump File    [{}]", filename)?;
+        if filename == STDIN_NAME {
+            continue;
+        }
+        if let Some(size) = input::path_size(filename) {
+            writeln!(out, "File Size:      [{}] bytes", size)?;
         }
     }
+    match length {
+        Some(length) => writeln!(out, "length:         [{}]", length)?,
+        None => writeln!(out, "length:         [all]")?,
+    }
+    writeln!(out, "offset:         [{}]", offset)
+}
+
+fn run_patch(args: &PatchArgs, row_format: &RowFormat) -> Result<(), Error> {
+    let data: Pattern = match (&args.bytes, &args.from_file) {
+        (Some(bytes), _) => search::parse_pattern(bytes, false).map_err(Error::Message)?,
+        (None, Some(path)) => match fs::read(path) {
+            Ok(data) if !data.is_empty() => data.into_iter().map(Some).collect(),
+            Ok(_) => {
+                return Err(Error::Message(format!(
+                    "[{}] is empty, nothing to patch",
+                    path
+                )))
+            }
+            Err(e) => return Err(Error::Message(format!("Failed to read [{}] {}", path, e))),
+        },
+        (None, None) => unreachable!("clap requires --bytes or --from-file"),
+    };
+    let options = PatchOptions {
+        dry_run: args.dry_run,
+        backup: args.backup,
+        allow_extend: args.allow_extend,
+    };
+    let mut out = io::stdout().lock();
+    patch::patch_file(&mut out, &args.file, args.at, &data, row_format, &options)
+}
+
+/// Inputs that failed were reported by the stream as it went on, and only
+/// change the exit status at the end
+fn inputs_result(input: &InputStream) -> Result<(), Error> {
+    match input.had_error() {
+        true => Err(Error::InputsFailed),
+        false => Ok(()),
+    }
+}
+
+/// Templates need random access to the structures, so the whole input is
+/// read first and annotated before dumping the offset/length window of it
+fn dump_with_template<W: io::Write>(
+    input: &mut InputStream,
+    dumper: Dumper<W>,
+    template: &Template,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<(), Error> {
+    let mut data: Vec<u8> = Vec::new();
+    // input errors are reported by the stream
+    let _ = input.read_to_end(&mut data);
+
+    let annotations = template::annotate(template, &data).map_err(Error::Message)?;
+    let mut dumper = dumper.with_annotations(annotations);
+
+    let size = data.len() as u64;
+    if offset > size {
+        return Err(Error::SkipPastEnd { offset, size });
+    }
+    let end = match length {
+        Some(length) => offset.saturating_add(length).min(size),
+        None => size,
+    };
+    dumper.write(&data[offset as usize..end as usize])?;
+    dumper.finish()
 }
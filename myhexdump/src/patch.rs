@@ -0,0 +1,174 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::FileExt;
+
+use crate::error::Error;
+use crate::format::{Layout, RowFormat};
+use crate::search::Pattern;
+use crate::size::Offset;
+
+pub struct PatchOptions {
+    pub dry_run: bool,
+    pub backup: bool,
+    pub allow_extend: bool,
+}
+
+/// Bytes at offset, shorter at the end of the file
+fn read_range(file: &fs::File, offset: u64, len: u64) -> Result<Vec<u8>, Error> {
+    let mut data = vec![0; len as usize];
+    let mut filled = 0;
+    while filled < data.len() {
+        match file.read_at(&mut data[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) => {
+                return Err(Error::Message(format!(
+                    "Failed to read at {:#x} ({})",
+                    offset, e
+                )))
+            }
+        }
+    }
+    data.truncate(filled);
+    Ok(data)
+}
+
+fn print_rows<W: Write>(
+    out: &mut W,
+    format: &RowFormat,
+    offset: u64,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut rows: Vec<u8> = Vec::new();
+    for (idx, row) in data.chunks(format.row_size()).enumerate() {
+        format.write_row(&mut rows, offset + (idx * format.row_size()) as u64, row);
+        // custom formats print their own newlines, the rows still have to
+        // end on one before the next heading
+        if format.layout() != Layout::Custom {
+            rows.push(b'\n');
+        }
+    }
+    if !rows.ends_with(b"\n") {
+        rows.push(b'\n');
+    }
+    out.write_all(&rows)
+}
+
+/// Overwrite the bytes of filename at `at` with patch, None bytes are left
+/// untouched. Files never grow unless allowed, and the written bytes are read
+/// back to check them.
+pub fn patch_file<W: Write>(
+    out: &mut W,
+    filename: &str,
+    at: Offset,
+    patch: &Pattern,
+    format: &RowFormat,
+    options: &PatchOptions,
+) -> Result<(), Error> {
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(!options.dry_run)
+        .open(filename)
+        .map_err(|e| Error::Message(format!("Failed to open file [{}] {}", filename, e)))?;
+    let meta = file
+        .metadata()
+        .map_err(|e| Error::Message(format!("Failed to read metadata of [{}] {}", filename, e)))?;
+    if !meta.is_file() {
+        return Err(Error::Message(format!(
+            "[{}] is not a regular file",
+            filename
+        )));
+    }
+    let size = meta.len();
+    let offset = at.resolve(size);
+    let end = offset + patch.len() as u64;
+    if end > size && !options.allow_extend {
+        return Err(Error::Message(format!(
+            "patching {} bytes at {:#x} would extend [{}] past its size of {} bytes, use --allow-extend",
+            patch.len(),
+            offset,
+            filename,
+            size
+        )));
+    }
+
+    // whole rows around the patch, bytes past the end become zeros
+    let row_size = format.row_size() as u64;
+    let rows_start = offset - offset % row_size;
+    let rows_end = end.div_ceil(row_size) * row_size;
+    let before = read_range(
+        &file,
+        rows_start,
+        rows_end.min(size).saturating_sub(rows_start),
+    )?;
+    let mut after = before.clone();
+    after.resize(((end.max(size)).min(rows_end) - rows_start) as usize, 0);
+    let patch_start = (offset - rows_start) as usize;
+    for (idx, byte) in patch.iter().enumerate() {
+        if let Some(byte) = byte {
+            after[patch_start + idx] = *byte;
+        }
+    }
+
+    if options.dry_run {
+        return writeln!(out, "before:")
+            .and_then(|_| print_rows(out, format, rows_start, &before))
+            .and_then(|_| writeln!(out, "after:"))
+            .and_then(|_| print_rows(out, format, rows_start, &after))
+            .and_then(|_| writeln!(out, "dry run, [{}] was not modified", filename))
+            .and_then(|_| out.flush())
+            .map_err(Error::Output);
+    }
+
+    if options.backup {
+        let backup = format!("{}.bak", filename);
+        if fs::metadata(&backup).is_ok() {
+            return Err(Error::Message(format!(
+                "backup [{}] already exists",
+                backup
+            )));
+        }
+        fs::copy(filename, &backup)
+            .map_err(|e| Error::Message(format!("Failed to write backup [{}] {}", backup, e)))?;
+    }
+
+    let written = &after[patch_start..patch_start + patch.len()];
+    file.write_all_at(written, offset)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| Error::Message(format!("Failed to write [{}] {}", filename, e)))?;
+
+    // read back what landed on disk
+    let new_size = file
+        .metadata()
+        .map_err(|e| Error::Message(format!("Failed to read metadata of [{}] {}", filename, e)))?
+        .len();
+    if new_size != size.max(end) {
+        return Err(Error::Message(format!(
+            "[{}] is now {} bytes long, expected {}",
+            filename,
+            new_size,
+            size.max(end)
+        )));
+    }
+    if read_range(&file, offset, written.len() as u64)? != written {
+        return Err(Error::Message(format!(
+            "bytes read back at {:#x} differ from the patch",
+            offset
+        )));
+    }
+    let grown = if new_size > size {
+        format!(", file grew to {} bytes", new_size)
+    } else {
+        String::new()
+    };
+    writeln!(
+        out,
+        "patched {} bytes at {:#x} in [{}]{}",
+        patch.len(),
+        offset,
+        filename,
+        grown
+    )
+    .and_then(|_| out.flush())
+    .map_err(Error::Output)
+}
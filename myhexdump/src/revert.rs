@@ -0,0 +1,270 @@
+use std::fs;
+use std::io::{self, BufRead, Seek, SeekFrom, Write};
+
+const ZERO_CHUNK: [u8; 4096] = [0; 4096];
+
+/// Where the rebuilt binary goes. Files are seeked so gaps between rows
+/// become sparse regions, stdout gets the gaps filled with zeros.
+enum Sink {
+    File(fs::File),
+    Stdout(io::Stdout),
+}
+
+struct SparseWriter {
+    sink: Sink,
+    position: u64,
+    end: u64,
+}
+
+impl SparseWriter {
+    fn write_zeros(out: &mut io::Stdout, mut count: u64) -> io::Result<()> {
+        while count > 0 {
+            let step = count.min(ZERO_CHUNK.len() as u64) as usize;
+            out.write_all(&ZERO_CHUNK[..step])?;
+            count -= step as u64;
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::File(file) => {
+                if offset != self.position {
+                    file.seek(SeekFrom::Start(offset))?;
+                }
+                file.write_all(data)?;
+            }
+            Sink::Stdout(out) => {
+                if offset < self.position {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "offset {:#x} goes backwards, use --output with a file",
+                            offset
+                        ),
+                    ));
+                }
+                SparseWriter::write_zeros(out, offset - self.position)?;
+                out.write_all(data)?;
+            }
+        }
+        self.position = offset + data.len() as u64;
+        self.end = self.end.max(self.position);
+        Ok(())
+    }
+
+    /// Make sure the output is at least size bytes long
+    fn extend_to(&mut self, size: u64) -> io::Result<()> {
+        if size <= self.end {
+            return Ok(());
+        }
+        match &mut self.sink {
+            Sink::File(file) => file.set_len(size)?,
+            Sink::Stdout(out) => {
+                SparseWriter::write_zeros(out, size - self.position)?;
+                self.position = size;
+            }
+        }
+        self.end = size;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.sink {
+            Sink::File(file) => file.flush(),
+            Sink::Stdout(out) => out.flush(),
+        }
+    }
+}
+
+pub struct Reverter {
+    writer: SparseWriter,
+    plain: bool,
+    /// offset of the next byte in plain mode
+    next_offset: u64,
+    /// pending high nibble in plain mode
+    nibble: Option<u8>,
+    last_row: Option<(u64, Vec<u8>)>,
+    squeezing: bool,
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+fn decode_hex(token: &str) -> Option<Vec<u8>> {
+    if !token.len().is_multiple_of(2) {
+        return None;
+    }
+    token
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| Some(hex_value(pair[0])? << 4 | hex_value(pair[1])?))
+        .collect()
+}
+
+impl Reverter {
+    /// Write to the given file, or stdout when None
+    pub fn new(output: Option<&str>, plain: bool) -> Result<Reverter, String> {
+        let sink = match output {
+            Some(path) => match fs::File::create(path) {
+                Ok(file) => Sink::File(file),
+                Err(e) => return Err(format!("Failed to create file [{}], reason: {}", path, e)),
+            },
+            None => Sink::Stdout(io::stdout()),
+        };
+        Ok(Reverter {
+            writer: SparseWriter {
+                sink,
+                position: 0,
+                end: 0,
+            },
+            plain,
+            next_offset: 0,
+            nibble: None,
+            last_row: None,
+            squeezing: false,
+        })
+    }
+
+    /// Parse a whole dump and write the binary out
+    pub fn run<R: BufRead>(&mut self, mut reader: R) -> Result<(), String> {
+        let mut line_number = 0;
+        let mut raw_line: Vec<u8> = Vec::new();
+        loop {
+            raw_line.clear();
+            match reader.read_until(b'\n', &mut raw_line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Err(format!("Failed to read dump ({})", e)),
+            }
+            line_number += 1;
+            let line = String::from_utf8_lossy(&raw_line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            let result = if self.plain {
+                self.plain_line(line)
+            } else {
+                self.dump_line(line)
+            };
+            if let Err(e) = result {
+                return Err(format!("line {}: {}", line_number, e));
+            }
+        }
+        if let Err(e) = self.writer.flush() {
+            return Err(format!("Failed to write output ({})", e));
+        }
+        if self.nibble.is_some() {
+            return Err("odd number of hex digits, the last one has no pair".to_string());
+        }
+        Ok(())
+    }
+
+    /// Plain hex (xxd -p), whitespace is ignored and offsets are implicit
+    fn plain_line(&mut self, line: &str) -> Result<(), String> {
+        let mut data: Vec<u8> = Vec::new();
+        for digit in line.bytes() {
+            if digit.is_ascii_whitespace() {
+                continue;
+            }
+            let value = match hex_value(digit) {
+                Some(value) => value,
+                None => return Err(format!("invalid hex digit [{}]", digit as char)),
+            };
+            match self.nibble.take() {
+                Some(high) => data.push(high << 4 | value),
+                None => self.nibble = Some(value),
+            }
+        }
+        self.writer
+            .write_at(self.next_offset, &data)
+            .map_err(|e| e.to_string())?;
+        self.next_offset += data.len() as u64;
+        Ok(())
+    }
+
+    /// One line of our own dump, hexdump -C or xxd output. Lines that do not
+    /// start with an offset (like our header) are ignored.
+    fn dump_line(&mut self, line: &str) -> Result<(), String> {
+        if line.trim() == "*" {
+            // hexdump squeezed identical rows, they are rebuilt on the next offset
+            self.squeezing = true;
+            return Ok(());
+        }
+
+        let digits = line.bytes().take_while(|c| c.is_ascii_hexdigit()).count();
+        if digits == 0 {
+            return Ok(());
+        }
+        let offset = match u64::from_str_radix(&line[..digits], 16) {
+            Ok(offset) => offset,
+            Err(_) => return Err(format!("invalid offset [{}]", &line[..digits])),
+        };
+        let rest = &line[digits..];
+
+        let data = if let Some(rest) = rest.strip_prefix(':') {
+            // xxd: hex groups end at the double space before the ascii text
+            let hex = rest.strip_prefix(' ').unwrap_or(rest);
+            let hex = hex.split("  ").next().unwrap_or("");
+            parse_tokens(hex, true)?
+        } else if rest.is_empty() {
+            // hexdump final offset line
+            self.fill_squeezed(offset)?;
+            self.writer.extend_to(offset).map_err(|e| e.to_string())?;
+            return Ok(());
+        } else if rest.starts_with(' ') {
+            // ours has a '|' after the offset, hexdump -C does not
+            let body = rest.trim_start();
+            let body = body.strip_prefix('|').unwrap_or(body);
+            let hex = body.split('|').next().unwrap_or("");
+            parse_tokens(hex, false)?
+        } else {
+            // something else starting with hex digits, like "File Size:"
+            return Ok(());
+        };
+
+        self.fill_squeezed(offset)?;
+        self.writer
+            .write_at(offset, &data)
+            .map_err(|e| e.to_string())?;
+        self.last_row = Some((offset, data));
+        Ok(())
+    }
+
+    /// Repeat the last row until reaching offset after a '*' line
+    fn fill_squeezed(&mut self, offset: u64) -> Result<(), String> {
+        if !self.squeezing {
+            return Ok(());
+        }
+        self.squeezing = false;
+
+        let (last_offset, row) = match &self.last_row {
+            Some((last_offset, row)) if !row.is_empty() => (*last_offset, row.clone()),
+            _ => return Err("'*' without a previous row".to_string()),
+        };
+        let mut position = last_offset + row.len() as u64;
+        while position < offset {
+            let step = (offset - position).min(row.len() as u64) as usize;
+            self.writer
+                .write_at(position, &row[..step])
+                .map_err(|e| e.to_string())?;
+            position += step as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Decode the whitespace separated hex tokens of a row. Our format and
+/// hexdump -C use single bytes, xxd uses groups of any even length.
+fn parse_tokens(hex: &str, grouped: bool) -> Result<Vec<u8>, String> {
+    let mut data: Vec<u8> = Vec::new();
+    for token in hex.split_whitespace() {
+        if !grouped && token.len() != 2 {
+            return Err(format!("invalid hex byte [{}]", token));
+        }
+        match decode_hex(token) {
+            Some(bytes) => data.extend(bytes),
+            None => return Err(format!("invalid hex value [{}]", token)),
+        }
+    }
+    Ok(data)
+}
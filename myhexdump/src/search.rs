@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::format::RowFormat;
+
+/// Pattern byte, None matches any byte
+pub type Pattern = Vec<Option<u8>>;
+
+/// Parse --find: a quoted "text" or hex bytes where ?? is a wildcard
+/// ("de ad ?? ef", "dead??ef"). Text is searched as UTF-8, or as UTF-16LE
+/// with utf16.
+pub fn parse_pattern(value: &str, utf16: bool) -> Result<Pattern, String> {
+    if let Some(text) = value
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    {
+        if text.is_empty() {
+            return Err("empty search text".to_string());
+        }
+        let bytes: Vec<u8> = if utf16 {
+            text.encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes())
+                .collect()
+        } else {
+            text.as_bytes().to_vec()
+        };
+        return Ok(bytes.into_iter().map(Some).collect());
+    }
+
+    let digits: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(format!(
+            "invalid hex pattern [{}], use pairs of hex digits or ?? (quote text like '\"text\"')",
+            value
+        ));
+    }
+    let mut pattern = Pattern::new();
+    for pair in digits.chunks(2) {
+        let byte: String = pair.iter().collect();
+        if byte == "??" {
+            pattern.push(None);
+            continue;
+        }
+        match u8::from_str_radix(&byte, 16) {
+            Ok(byte) => pattern.push(Some(byte)),
+            Err(_) => {
+                return Err(format!(
+                    "invalid hex byte [{}] in pattern, quote text like '\"text\"'",
+                    byte
+                ))
+            }
+        }
+    }
+    if pattern.iter().all(|byte| byte.is_none()) {
+        return Err("pattern has only wildcards".to_string());
+    }
+    Ok(pattern)
+}
+
+/// Whether pattern matches at the start of data
+pub fn matches_at(pattern: &Pattern, data: &[u8]) -> bool {
+    data.len() >= pattern.len()
+        && pattern
+            .iter()
+            .zip(data)
+            .all(|(expected, byte)| expected.is_none_or(|expected| expected == *byte))
+}
+
+/// Streaming search: data is fed in pieces of any size and only the bytes
+/// still needed for a match or its context rows are kept around.
+pub struct Searcher {
+    pattern: Pattern,
+    format: RowFormat,
+    context: u64,
+    /// start of the searched range, no context before it
+    start: u64,
+    window: Vec<u8>,
+    window_start: u64,
+    /// next offset to try the pattern at
+    scan: u64,
+    /// matches waiting for their trailing context rows
+    pending: VecDeque<u64>,
+    matches: u64,
+}
+
+impl Searcher {
+    pub fn new(pattern: Pattern, format: RowFormat, context: usize, start: u64) -> Searcher {
+        Searcher {
+            pattern,
+            format,
+            context: context as u64,
+            start,
+            window: Vec::new(),
+            window_start: start,
+            scan: start,
+            pending: VecDeque::new(),
+            matches: 0,
+        }
+    }
+
+    fn row_size(&self) -> u64 {
+        self.format.row_size() as u64
+    }
+
+    fn window_end(&self) -> u64 {
+        self.window_start + self.window.len() as u64
+    }
+
+    fn matches_at(&self, offset: u64) -> bool {
+        let idx = (offset - self.window_start) as usize;
+        matches_at(&self.pattern, &self.window[idx..])
+    }
+
+    /// First offset of the context rows before a match at offset
+    fn context_start(&self, offset: u64) -> u64 {
+        let row = offset / self.row_size();
+        (row.saturating_sub(self.context) * self.row_size()).max(self.start)
+    }
+
+    /// End of the context rows after a match at offset
+    fn context_end(&self, offset: u64) -> u64 {
+        let last = offset + self.pattern.len() as u64 - 1;
+        (last / self.row_size() + 1 + self.context) * self.row_size()
+    }
+
+    pub fn feed<W: Write>(&mut self, out: &mut W, data: &[u8]) -> io::Result<()> {
+        self.window.extend_from_slice(data);
+        let len = self.pattern.len() as u64;
+        while self.scan + len <= self.window_end() {
+            if self.matches_at(self.scan) {
+                self.pending.push_back(self.scan);
+            }
+            self.scan += 1;
+        }
+        self.print_ready(out, false)?;
+
+        // drop what no pending or future match can need anymore
+        let mut keep = self.context_start(self.scan);
+        if let Some(first) = self.pending.front() {
+            keep = keep.min(self.context_start(*first));
+        }
+        if keep > self.window_start {
+            self.window.drain(..(keep - self.window_start) as usize);
+            self.window_start = keep;
+        }
+        Ok(())
+    }
+
+    fn print_ready<W: Write>(&mut self, out: &mut W, at_end: bool) -> io::Result<()> {
+        while let Some(offset) = self.pending.front().copied() {
+            let end = self.context_end(offset);
+            if !at_end && end > self.window_end() {
+                break;
+            }
+            self.pending.pop_front();
+            self.print_match(out, offset, end.min(self.window_end()))?;
+        }
+        Ok(())
+    }
+
+    fn print_match<W: Write>(&mut self, out: &mut W, offset: u64, end: u64) -> io::Result<()> {
+        if self.context > 0 && self.matches > 0 {
+            writeln!(out, "--")?;
+        }
+        self.matches += 1;
+        writeln!(out, "match at {:#x} ({})", offset, offset)?;
+        if self.context == 0 {
+            return Ok(());
+        }
+        let mut row: Vec<u8> = Vec::new();
+        let mut row_start = self.context_start(offset);
+        while row_start < end {
+            let row_end = ((row_start / self.row_size() + 1) * self.row_size()).min(end);
+            let from = (row_start - self.window_start) as usize;
+            let to = (row_end - self.window_start) as usize;
+            row.clear();
+            self.format
+                .write_row(&mut row, row_start, &self.window[from..to]);
+            row.push(b'\n');
+            out.write_all(&row)?;
+            row_start = row_end;
+        }
+        Ok(())
+    }
+
+    /// Print the matches still waiting for context, returns how many were found
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<u64> {
+        self.print_ready(out, true)?;
+        match self.matches {
+            0 => writeln!(out, "No matches")?,
+            1 => writeln!(out, "1 match")?,
+            count => writeln!(out, "{} matches", count)?,
+        }
+        out.flush()?;
+        Ok(self.matches)
+    }
+}
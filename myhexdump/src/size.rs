@@ -0,0 +1,140 @@
+/// Start of the dump given with -s, negative values count back from the end
+/// of the input like xxd does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Offset {
+    FromStart(u64),
+    FromEnd(u64),
+}
+
+impl Offset {
+    /// Absolute offset for an input of total_size bytes. Offsets from the
+    /// end going past the beginning start at 0, same as tail -c.
+    pub fn resolve(&self, total_size: u64) -> u64 {
+        match self {
+            Offset::FromStart(offset) => *offset,
+            Offset::FromEnd(offset) => total_size.saturating_sub(*offset),
+        }
+    }
+}
+
+fn suffix_multiplier(suffix: &str) -> Option<u64> {
+    let multiplier = match suffix {
+        "" => 1,
+        // hexdump style
+        "b" => 512,
+        "k" | "K" | "KiB" => 1 << 10,
+        "m" | "M" | "MiB" => 1 << 20,
+        "g" | "G" | "GiB" => 1 << 30,
+        // SI units, like dd and head
+        "kB" | "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        _ => return None,
+    };
+    Some(multiplier)
+}
+
+/// Parse a byte count like hexdump does: decimal, hex with a 0x prefix or
+/// octal with a leading 0, optionally followed by a unit suffix (1K, 2MiB, 4kB).
+/// A lone 0 before a suffix (0K) is just zero.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let (digits, radix) = if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if value.len() > 1 && value.starts_with('0') && value.as_bytes()[1].is_ascii_digit() {
+        (&value[1..], 8)
+    } else {
+        (value, 10)
+    };
+
+    let end = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let (number, suffix) = digits.split_at(end);
+    if number.is_empty() {
+        return Err(format!("invalid number [{}]", value));
+    }
+    let number = match u64::from_str_radix(number, radix) {
+        Ok(number) => number,
+        Err(e) => return Err(format!("invalid number [{}] ({})", value, e)),
+    };
+    let multiplier = match suffix_multiplier(suffix) {
+        Some(multiplier) => multiplier,
+        None => return Err(format!("unknown size suffix [{}]", suffix)),
+    };
+    match number.checked_mul(multiplier) {
+        Some(size) => Ok(size),
+        None => Err(format!("size [{}] is too large", value)),
+    }
+}
+
+/// Parse the -s argument, a size with an optional sign. A '-' counts from
+/// the end of the input, '+' is accepted for xxd compatibility.
+pub fn parse_offset(value: &str) -> Result<Offset, String> {
+    if let Some(value) = value.strip_prefix('-') {
+        Ok(Offset::FromEnd(parse_size(value)?))
+    } else {
+        let value = value.strip_prefix('+').unwrap_or(value);
+        Ok(Offset::FromStart(parse_size(value)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size_radix() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("42"), Ok(42));
+        assert_eq!(parse_size("0x1f"), Ok(0x1f));
+        assert_eq!(parse_size("0XFF"), Ok(0xff));
+        assert_eq!(parse_size("010"), Ok(8));
+        assert_eq!(parse_size("00"), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_size_suffix() {
+        assert_eq!(parse_size("1K"), Ok(1024));
+        assert_eq!(parse_size("2MiB"), Ok(2 << 20));
+        assert_eq!(parse_size("4kB"), Ok(4000));
+        assert_eq!(parse_size("3b"), Ok(1536));
+        assert_eq!(parse_size("0x10K"), Ok(16 << 10));
+        assert_eq!(parse_size("010K"), Ok(8 << 10));
+    }
+
+    #[test]
+    fn test_parse_size_zero_with_suffix() {
+        assert_eq!(parse_size("0K"), Ok(0));
+        assert_eq!(parse_size("0MiB"), Ok(0));
+        assert_eq!(parse_size("0kB"), Ok(0));
+        // b is the 512 byte block suffix
+        assert_eq!(parse_size("0b"), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_size_invalid() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("0x").is_err());
+        assert!(parse_size("0xK").is_err());
+        assert!(parse_size("0xg").is_err());
+        assert!(parse_size("08").is_err());
+        assert!(parse_size("1Q").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("16GiB0").is_err());
+        assert!(parse_size("99999999999999999999").is_err());
+        assert!(parse_size("0xffffffffffffffffK").is_err());
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("0K"), Ok(Offset::FromStart(0)));
+        assert_eq!(parse_offset("+0x10"), Ok(Offset::FromStart(16)));
+        assert_eq!(parse_offset("-1K"), Ok(Offset::FromEnd(1024)));
+        assert_eq!(Offset::FromEnd(1024).resolve(100), 0);
+        assert_eq!(Offset::FromEnd(10).resolve(100), 90);
+    }
+}
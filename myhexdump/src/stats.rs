@@ -0,0 +1,187 @@
+use std::io::{self, Write};
+
+/// Block size when neither --block-size nor the input size are known
+pub const DEFAULT_BLOCK_SIZE: u64 = 4096;
+
+/// Rows the entropy graph aims for when the block size is picked for the input
+const GRAPH_ROWS: u64 = 64;
+const MIN_BLOCK_SIZE: u64 = 256;
+
+/// Width of the entropy bars, for the full 8 bits per byte
+const BAR_WIDTH: usize = 40;
+
+/// Blocks above this many bits per byte are most likely compressed or encrypted
+const HIGH_ENTROPY: f64 = 7.5;
+
+const MOST_FREQUENT: usize = 8;
+
+/// Eighths of a cell, for bars with sub-character precision
+const PARTIAL_BLOCKS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+/// Histogram cells, from a few occurrences to the most frequent byte
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Block size giving about GRAPH_ROWS blocks for length bytes
+pub fn block_size_for(length: u64) -> u64 {
+    length
+        .div_ceil(GRAPH_ROWS)
+        .next_power_of_two()
+        .max(MIN_BLOCK_SIZE)
+}
+
+/// Shannon entropy in bits per byte
+fn entropy(counts: &[u64; 256], total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .iter()
+        .filter(|count| **count != 0)
+        .map(|count| {
+            let p = *count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn bar(value: f64, max: f64, width: usize) -> String {
+    let eighths = ((value / max) * (width * 8) as f64).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        bar.push(PARTIAL_BLOCKS[eighths % 8]);
+    }
+    format!("{:<width$}", bar, width = width)
+}
+
+/// Byte histogram and entropy of data fed in pieces, overall and per block
+pub struct Stats {
+    block_size: u64,
+    start_offset: u64,
+    counts: [u64; 256],
+    total: u64,
+    block_counts: [u64; 256],
+    block_len: u64,
+    /// entropy and length of every finished block
+    blocks: Vec<(f64, u64)>,
+}
+
+impl Stats {
+    pub fn new(block_size: u64, start_offset: u64) -> Stats {
+        Stats {
+            block_size: block_size.max(1),
+            start_offset,
+            counts: [0; 256],
+            total: 0,
+            block_counts: [0; 256],
+            block_len: 0,
+            blocks: Vec::new(),
+        }
+    }
+
+    fn end_block(&mut self) {
+        for (count, block_count) in self.counts.iter_mut().zip(self.block_counts) {
+            *count += block_count;
+        }
+        self.total += self.block_len;
+        self.blocks
+            .push((entropy(&self.block_counts, self.block_len), self.block_len));
+        self.block_counts = [0; 256];
+        self.block_len = 0;
+    }
+
+    pub fn feed(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = ((self.block_size - self.block_len) as usize).min(data.len());
+            for byte in &data[..take] {
+                self.block_counts[*byte as usize] += 1;
+            }
+            self.block_len += take as u64;
+            data = &data[take..];
+            if self.block_len == self.block_size {
+                self.end_block();
+            }
+        }
+    }
+
+    pub fn finish(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.block_len > 0 {
+            self.end_block();
+        }
+        if self.total == 0 {
+            writeln!(out, "No data")?;
+            return out.flush();
+        }
+
+        let end = self.start_offset + self.total;
+        writeln!(
+            out,
+            "Range:    {:#x} - {:#x} ({} bytes)",
+            self.start_offset, end, self.total
+        )?;
+        writeln!(
+            out,
+            "Entropy:  {:.4} bits per byte",
+            entropy(&self.counts, self.total)
+        )?;
+        let distinct = self.counts.iter().filter(|count| **count != 0).count();
+        writeln!(out, "Distinct: {} of 256 byte values", distinct)?;
+
+        writeln!(out)?;
+        writeln!(out, "Most frequent bytes:")?;
+        let mut frequent: Vec<(usize, u64)> = self
+            .counts
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, count)| *count != 0)
+            .collect();
+        frequent.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        for (byte, count) in frequent.iter().take(MOST_FREQUENT) {
+            writeln!(
+                out,
+                "  {:#04x}  {:>10}  {:6.2}%",
+                byte,
+                count,
+                *count as f64 * 100.0 / self.total as f64
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "Byte histogram (one column per byte value):")?;
+        writeln!(out, "        0123456789abcdef")?;
+        let max = self.counts.iter().copied().max().unwrap_or(1) as f64;
+        for (row, counts) in self.counts.chunks(16).enumerate() {
+            let cells: String = counts
+                .iter()
+                .map(|count| match count {
+                    0 => ' ',
+                    count => {
+                        let level = (*count as f64 / max * LEVELS.len() as f64).ceil() as usize;
+                        LEVELS[level.clamp(1, LEVELS.len()) - 1]
+                    }
+                })
+                .collect();
+            writeln!(out, "  {:#04x}  {}", row * 16, cells)?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Entropy per {} byte block (0 to 8 bits per byte, * above {}):",
+            self.block_size, HIGH_ENTROPY
+        )?;
+        let mut offset = self.start_offset;
+        for (entropy, len) in self.blocks.iter() {
+            let marker = if *entropy > HIGH_ENTROPY { " *" } else { "" };
+            writeln!(
+                out,
+                "  {:08x}  {:.2} |{}|{}",
+                offset,
+                entropy,
+                bar(*entropy, 8.0, BAR_WIDTH),
+                marker
+            )?;
+            offset += len;
+        }
+        out.flush()
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{self, Write};
+
+use crate::format::is_printable;
+
+#[derive(Default)]
+struct Run {
+    start: u64,
+    text: String,
+}
+
+/// Extracts runs of printable ASCII and UTF-16LE (printable ASCII followed by
+/// a zero byte) from data fed in pieces, printing them like strings -t x.
+pub struct StringsExtractor {
+    min_length: usize,
+    /// offset of the next byte fed
+    offset: u64,
+    ascii: Run,
+    /// UTF-16 runs starting at even and odd offsets
+    wide: [Run; 2],
+    previous: Option<u8>,
+}
+
+impl StringsExtractor {
+    pub fn new(min_length: usize, start_offset: u64) -> StringsExtractor {
+        StringsExtractor {
+            min_length: min_length.max(1),
+            offset: start_offset,
+            ascii: Run::default(),
+            wide: [Run::default(), Run::default()],
+            previous: None,
+        }
+    }
+
+    fn end_run<W: Write>(
+        out: &mut W,
+        run: &mut Run,
+        min_length: usize,
+        wide: bool,
+    ) -> io::Result<()> {
+        if run.text.len() >= min_length {
+            if wide {
+                writeln!(out, "{:>7x} [utf16] {}", run.start, run.text)?;
+            } else {
+                writeln!(out, "{:>7x} {}", run.start, run.text)?;
+            }
+        }
+        run.text.clear();
+        Ok(())
+    }
+
+    pub fn feed<W: Write>(&mut self, out: &mut W, data: &[u8]) -> io::Result<()> {
+        for byte in data.iter().copied() {
+            if is_printable(byte) {
+                if self.ascii.text.is_empty() {
+                    self.ascii.start = self.offset;
+                }
+                self.ascii.text.push(byte as char);
+            } else {
+                StringsExtractor::end_run(out, &mut self.ascii, self.min_length, false)?;
+            }
+
+            // the pair made of the previous byte and this one
+            if let Some(previous) = self.previous {
+                let start = self.offset - 1;
+                let run = &mut self.wide[(start % 2) as usize];
+                if is_printable(previous) && byte == 0 {
+                    if run.text.is_empty() {
+                        run.start = start;
+                    }
+                    run.text.push(previous as char);
+                } else {
+                    StringsExtractor::end_run(out, run, self.min_length, true)?;
+                }
+            }
+            self.previous = Some(byte);
+            self.offset += 1;
+        }
+        Ok(())
+    }
+
+    /// Print the runs still open at the end of the input
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        StringsExtractor::end_run(out, &mut self.ascii, self.min_length, false)?;
+        for run in self.wide.iter_mut() {
+            StringsExtractor::end_run(out, run, self.min_length, true)?;
+        }
+        out.flush()
+    }
+}
@@ -0,0 +1,372 @@
+use std::fs;
+
+use serde::Deserialize;
+
+mod elf;
+mod gzip;
+mod png;
+mod zip;
+
+/// Decoded field shown in the annotation column of the row holding offset
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub offset: u64,
+    pub text: String,
+}
+
+/// Format given with --template, anything that is not a built-in name is
+/// the path of a custom TOML template
+#[derive(Clone, Debug, PartialEq)]
+pub enum Template {
+    Elf,
+    Png,
+    Zip,
+    Gzip,
+    Custom(String),
+}
+
+pub fn parse_template(value: &str) -> Result<Template, String> {
+    let template = match value {
+        "elf" => Template::Elf,
+        "png" => Template::Png,
+        "zip" => Template::Zip,
+        "gzip" | "gz" => Template::Gzip,
+        path if path.ends_with(".toml") => Template::Custom(path.to_string()),
+        _ => {
+            return Err(format!(
+                "unknown template [{}], use elf, png, zip, gzip or a .toml file",
+                value
+            ))
+        }
+    };
+    Ok(template)
+}
+
+/// Annotations for the whole input, sorted by offset
+pub fn annotate(template: &Template, data: &[u8]) -> Result<Vec<Annotation>, String> {
+    let mut annotator = Annotator::new(data);
+    match template {
+        Template::Elf => elf::annotate(&mut annotator)?,
+        Template::Png => png::annotate(&mut annotator)?,
+        Template::Zip => zip::annotate(&mut annotator)?,
+        Template::Gzip => gzip::annotate(&mut annotator)?,
+        Template::Custom(path) => annotate_custom(&mut annotator, path)?,
+    }
+    let mut annotations = annotator.annotations;
+    annotations.sort_by_key(|annotation| annotation.offset);
+    Ok(annotations)
+}
+
+/// Reads integers out of the input and records what they mean
+pub(crate) struct Annotator<'a> {
+    data: &'a [u8],
+    pub big_endian: bool,
+    annotations: Vec<Annotation>,
+}
+
+impl<'a> Annotator<'a> {
+    fn new(data: &'a [u8]) -> Annotator<'a> {
+        Annotator {
+            data,
+            big_endian: false,
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    pub fn bytes(&self, offset: u64, size: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(offset).ok()?;
+        let end = start.checked_add(usize::try_from(size).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    /// Unsigned integer of 1 to 8 bytes in the current byte order
+    pub fn read_uint(&self, offset: u64, size: u64) -> Option<u64> {
+        let bytes = self.bytes(offset, size)?;
+        let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+        if self.big_endian {
+            Some(bytes.iter().fold(0, fold))
+        } else {
+            Some(bytes.iter().rev().fold(0, fold))
+        }
+    }
+
+    pub fn note(&mut self, offset: u64, text: String) {
+        self.annotations.push(Annotation { offset, text });
+    }
+
+    /// Annotate an integer field in decimal
+    pub fn uint(&mut self, offset: u64, size: u64, name: &str) -> Option<u64> {
+        let value = self.read_uint(offset, size)?;
+        self.note(offset, format!("{}={}", name, value));
+        Some(value)
+    }
+
+    /// Annotate an integer field in hex, for offsets and addresses
+    pub fn hex(&mut self, offset: u64, size: u64, name: &str) -> Option<u64> {
+        let value = self.read_uint(offset, size)?;
+        self.note(offset, format!("{}={:#x}", name, value));
+        Some(value)
+    }
+
+    /// Annotate an integer field along with the name of its value
+    pub fn named(
+        &mut self,
+        offset: u64,
+        size: u64,
+        name: &str,
+        describe: fn(u64) -> Option<&'static str>,
+    ) -> Option<u64> {
+        let value = self.read_uint(offset, size)?;
+        let text = match describe(value) {
+            Some(meaning) => format!("{}={} ({})", name, value, meaning),
+            None => format!("{}={}", name, value),
+        };
+        self.note(offset, text);
+        Some(value)
+    }
+
+    /// Zero terminated string starting at offset, lossy utf-8
+    pub fn c_string(&self, offset: u64) -> String {
+        let start = match usize::try_from(offset) {
+            Ok(start) if start < self.data.len() => start,
+            _ => return String::new(),
+        };
+        let text = &self.data[start..];
+        let end = text
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(text.len());
+        String::from_utf8_lossy(&text[..end]).into_owned()
+    }
+}
+
+/// CRC-32 (IEEE) as used by png, gzip and zip
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = !0;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+/// A number in the template, either literal or the value of a field
+/// decoded earlier (like a table offset or an entry count in a header)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Value(u64),
+    Field(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldDef {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    /// byte width of bytes and str fields
+    size: Option<u64>,
+    /// number of elements for arrays
+    count: Option<Number>,
+    endian: Option<Endian>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StructDef {
+    name: String,
+    /// defaults to right after the previous struct
+    offset: Option<Number>,
+    /// repeat the struct, like a table of entries
+    count: Option<Number>,
+    endian: Option<Endian>,
+    fields: Vec<FieldDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CustomTemplate {
+    #[serde(default)]
+    endian: Endian,
+    #[serde(rename = "struct")]
+    structs: Vec<StructDef>,
+}
+
+const FIELD_TYPES: &[&str] = &[
+    "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64", "bytes", "str",
+];
+
+/// Arrays longer than this are shortened in the annotation
+const MAX_ARRAY_VALUES: usize = 8;
+
+fn resolve(number: &Number, values: &[(String, u64)]) -> Result<u64, String> {
+    match number {
+        Number::Value(value) => Ok(*value),
+        Number::Field(name) => match values.iter().rev().find(|(field, _)| field == name) {
+            Some((_, value)) => Ok(*value),
+            None => Err(format!("unknown field [{}] in template", name)),
+        },
+    }
+}
+
+/// Decode one value of a custom field, returns the text and the integer
+/// value (used by later counts and offsets) when there is one
+fn decode(annotator: &Annotator, field: &FieldDef, offset: u64) -> Option<(String, Option<u64>)> {
+    let signed = |value: u64, bits: u32| ((value << (64 - bits)) as i64) >> (64 - bits);
+    let (text, value) = match field.kind.as_str() {
+        "u8" | "u16" | "u32" | "u64" => {
+            let value = annotator.read_uint(offset, type_size(field)?)?;
+            let text = if value > 9 {
+                format!("{} ({:#x})", value, value)
+            } else {
+                value.to_string()
+            };
+            (text, Some(value))
+        }
+        "i8" | "i16" | "i32" | "i64" => {
+            let size = type_size(field)?;
+            let value = signed(annotator.read_uint(offset, size)?, size as u32 * 8);
+            (value.to_string(), Some(value as u64))
+        }
+        "f32" => {
+            let value = annotator.read_uint(offset, 4)? as u32;
+            (f32::from_bits(value).to_string(), None)
+        }
+        "f64" => {
+            let value = annotator.read_uint(offset, 8)?;
+            (f64::from_bits(value).to_string(), None)
+        }
+        "str" => {
+            let bytes = annotator.bytes(offset, type_size(field)?)?;
+            let end = bytes
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(bytes.len());
+            (
+                format!("{:?}", String::from_utf8_lossy(&bytes[..end])),
+                None,
+            )
+        }
+        // bytes
+        _ => {
+            let bytes = annotator.bytes(offset, type_size(field)?)?;
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            (hex.join(""), None)
+        }
+    };
+    Some((text, value))
+}
+
+fn type_size(field: &FieldDef) -> Option<u64> {
+    match field.kind.as_str() {
+        "u8" | "i8" => Some(1),
+        "u16" | "i16" => Some(2),
+        "u32" | "i32" | "f32" => Some(4),
+        "u64" | "i64" | "f64" => Some(8),
+        _ => field.size,
+    }
+}
+
+fn annotate_custom(annotator: &mut Annotator, path: &str) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Failed to read template [{}], reason: {}", path, e)),
+    };
+    let template: CustomTemplate = match toml::from_str(&text) {
+        Ok(template) => template,
+        Err(e) => return Err(format!("Invalid template [{}]: {}", path, e)),
+    };
+    for def in template.structs.iter() {
+        if def.fields.is_empty() {
+            return Err(format!("struct [{}] has no fields", def.name));
+        }
+        for field in def.fields.iter() {
+            if !FIELD_TYPES.contains(&field.kind.as_str()) {
+                return Err(format!(
+                    "field [{}] has unknown type [{}]",
+                    field.name, field.kind
+                ));
+            }
+            if type_size(field).unwrap_or_default() == 0 {
+                return Err(format!("field [{}] needs a size", field.name));
+            }
+        }
+    }
+
+    let mut values: Vec<(String, u64)> = Vec::new();
+    let mut position: u64 = 0;
+    for def in template.structs.iter() {
+        if let Some(offset) = &def.offset {
+            position = resolve(offset, &values)?;
+        }
+        let repeat = match &def.count {
+            Some(count) => resolve(count, &values)?,
+            None => 1,
+        };
+        for idx in 0..repeat {
+            if position >= annotator.len() {
+                return Ok(());
+            }
+            let prefix = match def.count {
+                Some(_) => format!("{}[{}]", def.name, idx),
+                None => def.name.clone(),
+            };
+            for field in def.fields.iter() {
+                let endian = field.endian.or(def.endian).unwrap_or(template.endian);
+                annotator.big_endian = endian == Endian::Big;
+                let size = type_size(field).unwrap_or_default();
+                let count = match &field.count {
+                    Some(count) => Some(resolve(count, &values)?),
+                    None => None,
+                };
+
+                let name = format!("{}.{}", prefix, field.name);
+                let elements = count.unwrap_or(1);
+                let mut texts: Vec<String> = Vec::new();
+                for element in 0..elements {
+                    let offset = position.saturating_add(element.saturating_mul(size));
+                    let (text, value) = match decode(annotator, field, offset) {
+                        Some(decoded) => decoded,
+                        None => break,
+                    };
+                    // only plain fields can be referenced, not array elements
+                    if let (Some(value), None) = (value, count) {
+                        values.push((field.name.clone(), value));
+                    }
+                    if texts.len() < MAX_ARRAY_VALUES {
+                        texts.push(text);
+                    }
+                }
+                let text = match count {
+                    Some(count) if count as usize > MAX_ARRAY_VALUES => {
+                        format!("{}[{}]=[{}, ...]", name, count, texts.join(", "))
+                    }
+                    Some(count) => format!("{}[{}]=[{}]", name, count, texts.join(", ")),
+                    None => match texts.pop() {
+                        Some(text) => format!("{}={}", name, text),
+                        None => return Ok(()),
+                    },
+                };
+                annotator.note(position, text);
+                position = position.saturating_add(elements.saturating_mul(size));
+            }
+        }
+    }
+    Ok(())
+}
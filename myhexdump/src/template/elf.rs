@@ -0,0 +1,226 @@
+use super::Annotator;
+
+/// Program and section headers past this are not annotated
+const MAX_HEADERS: u64 = 4096;
+
+fn class_name(value: u64) -> Option<&'static str> {
+    match value {
+        1 => Some("ELF32"),
+        2 => Some("ELF64"),
+        _ => None,
+    }
+}
+
+fn data_name(value: u64) -> Option<&'static str> {
+    match value {
+        1 => Some("little endian"),
+        2 => Some("big endian"),
+        _ => None,
+    }
+}
+
+fn osabi_name(value: u64) -> Option<&'static str> {
+    match value {
+        0 => Some("SYSV"),
+        3 => Some("Linux"),
+        9 => Some("FreeBSD"),
+        _ => None,
+    }
+}
+
+fn type_name(value: u64) -> Option<&'static str> {
+    match value {
+        0 => Some("NONE"),
+        1 => Some("REL"),
+        2 => Some("EXEC"),
+        3 => Some("DYN"),
+        4 => Some("CORE"),
+        _ => None,
+    }
+}
+
+fn machine_name(value: u64) -> Option<&'static str> {
+    match value {
+        0x03 => Some("x86"),
+        0x08 => Some("MIPS"),
+        0x14 => Some("PowerPC"),
+        0x15 => Some("PowerPC64"),
+        0x28 => Some("ARM"),
+        0x3e => Some("x86-64"),
+        0xb7 => Some("AArch64"),
+        0xf3 => Some("RISC-V"),
+        _ => None,
+    }
+}
+
+fn segment_name(value: u64) -> String {
+    let name = match value {
+        0 => "NULL",
+        1 => "LOAD",
+        2 => "DYNAMIC",
+        3 => "INTERP",
+        4 => "NOTE",
+        5 => "SHLIB",
+        6 => "PHDR",
+        7 => "TLS",
+        0x6474_e550 => "GNU_EH_FRAME",
+        0x6474_e551 => "GNU_STACK",
+        0x6474_e552 => "GNU_RELRO",
+        0x6474_e553 => "GNU_PROPERTY",
+        _ => return format!("{:#x}", value),
+    };
+    name.to_string()
+}
+
+fn section_type_name(value: u64) -> String {
+    let name = match value {
+        0 => "NULL",
+        1 => "PROGBITS",
+        2 => "SYMTAB",
+        3 => "STRTAB",
+        4 => "RELA",
+        5 => "HASH",
+        6 => "DYNAMIC",
+        7 => "NOTE",
+        8 => "NOBITS",
+        9 => "REL",
+        11 => "DYNSYM",
+        14 => "INIT_ARRAY",
+        15 => "FINI_ARRAY",
+        0x6fff_fff6 => "GNU_HASH",
+        0x6fff_fffe => "VERNEED",
+        0x6fff_ffff => "VERSYM",
+        _ => return format!("{:#x}", value),
+    };
+    name.to_string()
+}
+
+/// Offsets of the fields that move between ELF32 and ELF64 headers
+struct Layout {
+    word: u64,
+    phdr_offset: u64,
+    phdr_filesz: u64,
+    phdr_vaddr: u64,
+    shdr_type: u64,
+    shdr_offset: u64,
+    shdr_size: u64,
+}
+
+const ELF32: Layout = Layout {
+    word: 4,
+    phdr_offset: 4,
+    phdr_filesz: 16,
+    phdr_vaddr: 8,
+    shdr_type: 4,
+    shdr_offset: 16,
+    shdr_size: 20,
+};
+
+const ELF64: Layout = Layout {
+    word: 8,
+    phdr_offset: 8,
+    phdr_filesz: 32,
+    phdr_vaddr: 16,
+    shdr_type: 4,
+    shdr_offset: 24,
+    shdr_size: 32,
+};
+
+/// ELF header, program headers, section headers and where sections start
+pub fn annotate(elf: &mut Annotator) -> Result<(), String> {
+    if elf.bytes(0, 4) != Some(b"\x7fELF") {
+        return Err("not an ELF file (bad magic)".to_string());
+    }
+    elf.note(0, "magic=\\x7fELF".to_string());
+    let layout = match elf.named(4, 1, "class", class_name) {
+        Some(1) => ELF32,
+        Some(2) => ELF64,
+        _ => return Err("unknown ELF class".to_string()),
+    };
+    let data = elf.named(5, 1, "data", data_name);
+    elf.big_endian = data == Some(2);
+    elf.uint(6, 1, "version");
+    elf.named(7, 1, "osabi", osabi_name);
+
+    let word = layout.word;
+    elf.named(16, 2, "e_type", type_name);
+    elf.named(18, 2, "e_machine", machine_name);
+    elf.uint(20, 4, "e_version");
+    elf.hex(24, word, "e_entry");
+    let phoff = elf.hex(24 + word, word, "e_phoff").unwrap_or_default();
+    let shoff = elf.hex(24 + 2 * word, word, "e_shoff").unwrap_or_default();
+    let flags_offset = 24 + 3 * word;
+    elf.hex(flags_offset, 4, "e_flags");
+    elf.uint(flags_offset + 4, 2, "e_ehsize");
+    let phentsize = elf
+        .uint(flags_offset + 6, 2, "e_phentsize")
+        .unwrap_or_default();
+    let phnum = elf.uint(flags_offset + 8, 2, "e_phnum").unwrap_or_default();
+    let shentsize = elf
+        .uint(flags_offset + 10, 2, "e_shentsize")
+        .unwrap_or_default();
+    let shnum = elf
+        .uint(flags_offset + 12, 2, "e_shnum")
+        .unwrap_or_default();
+    let shstrndx = elf
+        .uint(flags_offset + 14, 2, "e_shstrndx")
+        .unwrap_or_default();
+
+    for idx in 0..phnum.min(MAX_HEADERS) {
+        let base = phoff.saturating_add(idx * phentsize);
+        let (Some(kind), Some(offset), Some(vaddr), Some(filesz)) = (
+            elf.read_uint(base, 4),
+            elf.read_uint(base.saturating_add(layout.phdr_offset), word),
+            elf.read_uint(base.saturating_add(layout.phdr_vaddr), word),
+            elf.read_uint(base.saturating_add(layout.phdr_filesz), word),
+        ) else {
+            break;
+        };
+        elf.note(
+            base,
+            format!(
+                "phdr[{}] {} offset={:#x} vaddr={:#x} filesz={:#x}",
+                idx,
+                segment_name(kind),
+                offset,
+                vaddr,
+                filesz
+            ),
+        );
+    }
+
+    // section names live in the section named by e_shstrndx
+    let strtab_header = shoff.saturating_add(shstrndx * shentsize);
+    let strtab = elf.read_uint(strtab_header.saturating_add(layout.shdr_offset), word);
+    for idx in 0..shnum.min(MAX_HEADERS) {
+        let base = shoff.saturating_add(idx * shentsize);
+        let (Some(name), Some(kind), Some(offset), Some(size)) = (
+            elf.read_uint(base, 4),
+            elf.read_uint(base.saturating_add(layout.shdr_type), 4),
+            elf.read_uint(base.saturating_add(layout.shdr_offset), word),
+            elf.read_uint(base.saturating_add(layout.shdr_size), word),
+        ) else {
+            break;
+        };
+        let name = match strtab {
+            Some(strtab) => elf.c_string(strtab.saturating_add(name)),
+            None => String::new(),
+        };
+        elf.note(
+            base,
+            format!(
+                "shdr[{}] {} {} offset={:#x} size={:#x}",
+                idx,
+                name,
+                section_type_name(kind),
+                offset,
+                size
+            ),
+        );
+        // NOBITS sections (.bss) take no room in the file
+        if kind != 0 && kind != 8 && size > 0 && offset < elf.len() {
+            elf.note(offset, format!("section {} start", name));
+        }
+    }
+    Ok(())
+}
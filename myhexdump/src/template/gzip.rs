@@ -0,0 +1,66 @@
+use super::Annotator;
+
+const FHCRC: u64 = 0x02;
+const FEXTRA: u64 = 0x04;
+const FNAME: u64 = 0x08;
+const FCOMMENT: u64 = 0x10;
+
+fn method_name(value: u64) -> Option<&'static str> {
+    match value {
+        8 => Some("deflate"),
+        _ => None,
+    }
+}
+
+fn os_name(value: u64) -> Option<&'static str> {
+    match value {
+        0 => Some("FAT"),
+        3 => Some("Unix"),
+        7 => Some("Macintosh"),
+        11 => Some("NTFS"),
+        255 => Some("unknown"),
+        _ => None,
+    }
+}
+
+/// Header fields, optional extra/name/comment, start of the deflate stream
+/// and the CRC32/ISIZE trailer of the (last) member
+pub fn annotate(gzip: &mut Annotator) -> Result<(), String> {
+    if gzip.bytes(0, 2) != Some(b"\x1f\x8b") {
+        return Err("not a gzip file (bad magic)".to_string());
+    }
+    gzip.note(0, "magic=1f8b".to_string());
+    gzip.named(2, 1, "method", method_name);
+    let flags = gzip.hex(3, 1, "flags").unwrap_or_default();
+    gzip.uint(4, 4, "mtime");
+    gzip.uint(8, 1, "xfl");
+    gzip.named(9, 1, "os", os_name);
+
+    let mut position: u64 = 10;
+    if flags & FEXTRA != 0 {
+        let extra = gzip.uint(position, 2, "xlen").unwrap_or_default();
+        position += 2 + extra;
+    }
+    if flags & FNAME != 0 {
+        let name = gzip.c_string(position);
+        gzip.note(position, format!("name={:?}", name));
+        position += name.len() as u64 + 1;
+    }
+    if flags & FCOMMENT != 0 {
+        let comment = gzip.c_string(position);
+        gzip.note(position, format!("comment={:?}", comment));
+        position += comment.len() as u64 + 1;
+    }
+    if flags & FHCRC != 0 {
+        gzip.hex(position, 2, "header_crc16");
+        position += 2;
+    }
+
+    let len = gzip.len();
+    if position + 8 <= len {
+        gzip.note(position, "deflate data".to_string());
+        gzip.hex(len - 8, 4, "crc32");
+        gzip.uint(len - 4, 4, "isize");
+    }
+    Ok(())
+}
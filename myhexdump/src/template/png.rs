@@ -0,0 +1,69 @@
+use super::{crc32, Annotator};
+
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn color_type_name(value: u64) -> Option<&'static str> {
+    match value {
+        0 => Some("grayscale"),
+        2 => Some("truecolor"),
+        3 => Some("indexed"),
+        4 => Some("grayscale+alpha"),
+        6 => Some("truecolor+alpha"),
+        _ => None,
+    }
+}
+
+/// Signature and every chunk: length, type, CRC check and the IHDR fields
+pub fn annotate(png: &mut Annotator) -> Result<(), String> {
+    if png.bytes(0, SIGNATURE.len() as u64) != Some(SIGNATURE) {
+        return Err("not a PNG file (bad signature)".to_string());
+    }
+    png.big_endian = true;
+    png.note(0, "signature".to_string());
+
+    let mut position = SIGNATURE.len() as u64;
+    while let Some(length) = png.uint(position, 4, "length") {
+        let kind = match png.bytes(position + 4, 4) {
+            Some(kind) => String::from_utf8_lossy(kind).into_owned(),
+            None => break,
+        };
+        png.note(position + 4, format!("chunk={}", kind));
+
+        let data_offset = position + 8;
+        if kind == "IHDR" {
+            png.uint(data_offset, 4, "width");
+            png.uint(data_offset + 4, 4, "height");
+            png.uint(data_offset + 8, 1, "bit_depth");
+            png.named(data_offset + 9, 1, "color_type", color_type_name);
+            png.uint(data_offset + 10, 1, "compression");
+            png.uint(data_offset + 11, 1, "filter");
+            png.uint(data_offset + 12, 1, "interlace");
+        }
+
+        let crc_offset = data_offset.saturating_add(length);
+        let stored = match png.read_uint(crc_offset, 4) {
+            Some(stored) => stored as u32,
+            None => {
+                png.note(position, format!("{} chunk is truncated", kind));
+                break;
+            }
+        };
+        // the CRC covers the chunk type and data
+        let computed = png
+            .bytes(position + 4, length + 4)
+            .map(crc32)
+            .unwrap_or_default();
+        let check = if computed == stored {
+            "ok".to_string()
+        } else {
+            format!("BAD, expected {:#010x}", computed)
+        };
+        png.note(crc_offset, format!("crc={:#010x} ({})", stored, check));
+
+        if kind == "IEND" {
+            break;
+        }
+        position = crc_offset + 4;
+    }
+    Ok(())
+}
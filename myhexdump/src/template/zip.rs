@@ -0,0 +1,97 @@
+use super::Annotator;
+
+const LOCAL_HEADER: u64 = 0x0403_4b50;
+const CENTRAL_HEADER: u64 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR: u64 = 0x0605_4b50;
+/// End of central directory record plus the longest possible comment
+const MAX_EOCD_SEARCH: u64 = 22 + 0xffff;
+
+fn method_name(value: u64) -> String {
+    match value {
+        0 => "stored".to_string(),
+        8 => "deflate".to_string(),
+        12 => "bzip2".to_string(),
+        14 => "lzma".to_string(),
+        93 => "zstd".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn find_end_of_central_dir(zip: &Annotator) -> Option<u64> {
+    let len = zip.len();
+    let first = len.saturating_sub(MAX_EOCD_SEARCH);
+    (first..len.saturating_sub(21))
+        .rev()
+        .find(|offset| zip.read_uint(*offset, 4) == Some(END_OF_CENTRAL_DIR))
+}
+
+/// Walk the central directory, annotating every entry and the local header
+/// and data it points to
+pub fn annotate(zip: &mut Annotator) -> Result<(), String> {
+    let eocd = match find_end_of_central_dir(zip) {
+        Some(eocd) => eocd,
+        None => return Err("not a zip file (no end of central directory)".to_string()),
+    };
+    let entries = zip.read_uint(eocd + 10, 2).unwrap_or_default();
+    let cd_size = zip.read_uint(eocd + 12, 4).unwrap_or_default();
+    let cd_offset = zip.read_uint(eocd + 16, 4).unwrap_or_default();
+    zip.note(
+        eocd,
+        format!(
+            "end of central dir entries={} cd_offset={:#x} cd_size={:#x}",
+            entries, cd_offset, cd_size
+        ),
+    );
+
+    let mut position = cd_offset;
+    for idx in 0..entries {
+        if zip.read_uint(position, 4) != Some(CENTRAL_HEADER) {
+            zip.note(position, format!("central dir entry {} is missing", idx));
+            break;
+        }
+        let field =
+            |offset: u64, size: u64| zip.read_uint(position + offset, size).unwrap_or_default();
+        let method = field(10, 2);
+        let crc = field(16, 4);
+        let compressed = field(20, 4);
+        let size = field(24, 4);
+        let name_len = field(28, 2);
+        let extra_len = field(30, 2);
+        let comment_len = field(32, 2);
+        let local = field(42, 4);
+        let name = zip
+            .bytes(position + 46, name_len)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .unwrap_or_default();
+        zip.note(
+            position,
+            format!(
+                "central dir[{}] name={:?} local_header={:#x}",
+                idx, name, local
+            ),
+        );
+
+        if zip.read_uint(local, 4) == Some(LOCAL_HEADER) {
+            zip.note(
+                local,
+                format!(
+                    "local header name={:?} method={} crc32={:#010x} compressed={} size={}",
+                    name,
+                    method_name(method),
+                    crc,
+                    compressed,
+                    size
+                ),
+            );
+            // the local name and extra field can differ from the central ones
+            let local_name = zip.read_uint(local + 26, 2).unwrap_or_default();
+            let local_extra = zip.read_uint(local + 28, 2).unwrap_or_default();
+            let data = local + 30 + local_name + local_extra;
+            if compressed > 0 {
+                zip.note(data, format!("data of {:?}", name));
+            }
+        }
+        position += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(())
+}
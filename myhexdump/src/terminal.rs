@@ -0,0 +1,167 @@
+use std::fs;
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+/// How long to wait for the rest of an escape sequence after ESC
+const ESCAPE_TIMEOUT_MS: i32 = 50;
+
+/// Ctrl-C and Ctrl-D, raw mode delivers them as plain bytes instead of a
+/// signal and an end of file
+const CTRL_C: u8 = 0x03;
+const CTRL_D: u8 = 0x04;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    /// Ctrl-C or Ctrl-D
+    Quit,
+    Char(char),
+}
+
+/// The controlling terminal in raw mode on the alternate screen. Keys are
+/// read from /dev/tty so stdin can be anything. Everything is restored when
+/// dropped.
+pub struct Terminal {
+    tty: fs::File,
+    original: libc::termios,
+}
+
+impl Terminal {
+    pub fn open() -> io::Result<Terminal> {
+        let tty = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")?;
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        if unsafe { libc::tcgetattr(tty.as_raw_fd(), &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        if unsafe { libc::tcsetattr(tty.as_raw_fd(), libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut terminal = Terminal { tty, original };
+        // alternate screen, hidden cursor
+        terminal.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        Ok(terminal)
+    }
+
+    /// Rows and columns, 24x80 if the size is unknown
+    pub fn size(&self) -> (usize, usize) {
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        let ret = unsafe { libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+        if ret != 0 || size.ws_row == 0 || size.ws_col == 0 {
+            return (24, 80);
+        }
+        (size.ws_row as usize, size.ws_col as usize)
+    }
+
+    pub fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.tty.write_all(data)?;
+        self.tty.flush()
+    }
+
+    /// Next byte, None if nothing arrived within timeout_ms (-1 waits forever)
+    fn read_byte(&self, timeout_ms: i32) -> io::Result<Option<u8>> {
+        let mut poll = libc::pollfd {
+            fd: self.tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            let ret = unsafe { libc::poll(&mut poll, 1, timeout_ms) };
+            if ret < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            if ret == 0 {
+                return Ok(None);
+            }
+            let mut byte: u8 = 0;
+            let read = unsafe {
+                libc::read(
+                    self.tty.as_raw_fd(),
+                    &mut byte as *mut u8 as *mut libc::c_void,
+                    1,
+                )
+            };
+            return match read {
+                1 => Ok(Some(byte)),
+                0 => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                _ => Err(io::Error::last_os_error()),
+            };
+        }
+    }
+
+    pub fn read_key(&self) -> io::Result<Key> {
+        loop {
+            let byte = match self.read_byte(-1)? {
+                Some(byte) => byte,
+                None => continue,
+            };
+            let key = match byte {
+                b'\r' | b'\n' => Key::Enter,
+                0x7f | 0x08 => Key::Backspace,
+                CTRL_C | CTRL_D => Key::Quit,
+                0x1b => self.read_escape()?,
+                byte if byte.is_ascii() && !byte.is_ascii_control() => Key::Char(byte as char),
+                _ => continue,
+            };
+            return Ok(key);
+        }
+    }
+
+    /// Decode CSI/SS3 sequences (ESC [ A, ESC [ 5 ~, ESC O H...), a lone
+    /// ESC is the escape key
+    fn read_escape(&self) -> io::Result<Key> {
+        let kind = match self.read_byte(ESCAPE_TIMEOUT_MS)? {
+            Some(kind @ (b'[' | b'O')) => kind,
+            _ => return Ok(Key::Escape),
+        };
+        let mut params = String::new();
+        loop {
+            let byte = match self.read_byte(ESCAPE_TIMEOUT_MS)? {
+                Some(byte) => byte,
+                None => return Ok(Key::Escape),
+            };
+            if byte.is_ascii_digit() || byte == b';' {
+                params.push(byte as char);
+                continue;
+            }
+            let key = match (kind, byte, params.as_str()) {
+                (_, b'A', _) => Key::Up,
+                (_, b'B', _) => Key::Down,
+                (_, b'C', _) => Key::Right,
+                (_, b'D', _) => Key::Left,
+                (_, b'H', _) | (b'[', b'~', "1" | "7") => Key::Home,
+                (_, b'F', _) | (b'[', b'~', "4" | "8") => Key::End,
+                (b'[', b'~', "5") => Key::PageUp,
+                (b'[', b'~', "6") => Key::PageDown,
+                _ => Key::Escape,
+            };
+            return Ok(key);
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.write_all(b"\x1b[?25h\x1b[?1049l");
+        unsafe { libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.original) };
+    }
+}
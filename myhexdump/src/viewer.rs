@@ -0,0 +1,553 @@
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+use crate::format::{format_float, is_printable, ROW_SIZE};
+use crate::input;
+use crate::search::{self, Pattern};
+use crate::size::{self, Offset};
+use crate::terminal::{Key, Terminal};
+
+/// Piece of the file read at once while searching
+const SEARCH_CHUNK_SIZE: usize = 1 << 20;
+
+/// Lines around the rows: title, two decode lines and the status line
+const CHROME_LINES: usize = 4;
+
+const HELP: &str =
+    "q quit  g goto  / ? search  n N next/previous match  m bookmark  [ ] previous/next bookmark";
+
+const REVERSE: &str = "\x1b[7m";
+const BOOKMARK: &str = "\x1b[1;4;33m";
+const RESET: &str = "\x1b[0m";
+
+/// Full-screen pager over one file. Only the rows on screen are read, so
+/// opening a huge file is instant.
+pub struct Viewer {
+    file: File,
+    name: String,
+    size: u64,
+    offset_width: usize,
+    /// first offset on screen, always at a row start
+    top: u64,
+    cursor: u64,
+    bookmarks: BTreeSet<u64>,
+    search: Option<Pattern>,
+    /// shown in the status line until the next key
+    message: String,
+}
+
+/// Open filename in the viewer with the cursor at start
+pub fn run(filename: &str, start: Offset) -> Result<(), String> {
+    let file =
+        File::open(filename).map_err(|e| format!("Failed to open file [{}] {}", filename, e))?;
+    let size = match input::file_size(&file) {
+        Some(size) => size,
+        None => {
+            return Err(format!(
+                "--interactive needs a regular file or a block device, [{}] is neither",
+                filename
+            ))
+        }
+    };
+    let offset = start.resolve(size);
+    if offset >= size && size > 0 {
+        return Err(format!(
+            "Skipped[{}] past the end of input size[{}]",
+            offset, size
+        ));
+    }
+
+    let mut viewer = Viewer {
+        file,
+        name: filename.to_string(),
+        size,
+        offset_width: format!("{:x}", size).len().max(8),
+        top: 0,
+        cursor: offset,
+        bookmarks: BTreeSet::new(),
+        search: None,
+        message: String::new(),
+    };
+    let mut terminal =
+        Terminal::open().map_err(|e| format!("Failed to open the terminal ({})", e))?;
+    viewer
+        .run(&mut terminal)
+        .map_err(|e| format!("Terminal error ({})", e))
+}
+
+impl Viewer {
+    fn run(&mut self, terminal: &mut Terminal) -> io::Result<()> {
+        loop {
+            let rows = self.rows(terminal);
+            self.scroll_to_cursor(rows);
+            let status = if self.message.is_empty() {
+                HELP.to_string()
+            } else {
+                std::mem::take(&mut self.message)
+            };
+            self.draw(terminal, &status)?;
+
+            let page = (rows * ROW_SIZE) as i64;
+            match terminal.read_key()? {
+                Key::Char('q') | Key::Escape | Key::Quit => return Ok(()),
+                Key::Up | Key::Char('k') => self.move_cursor(-(ROW_SIZE as i64)),
+                Key::Down | Key::Char('j') => self.move_cursor(ROW_SIZE as i64),
+                Key::Left | Key::Char('h') => self.move_cursor(-1),
+                Key::Right | Key::Char('l') => self.move_cursor(1),
+                Key::PageUp | Key::Char('b') => {
+                    self.top = self.top.saturating_sub(page as u64);
+                    self.move_cursor(-page);
+                }
+                Key::PageDown | Key::Char(' ') => {
+                    if self.top + (page as u64) < self.size {
+                        self.top += page as u64;
+                    }
+                    self.move_cursor(page);
+                }
+                Key::Home => self.cursor = 0,
+                Key::End | Key::Char('G') => self.cursor = self.last_offset(),
+                Key::Char('g') => {
+                    if let Some(value) = self.prompt(terminal, "goto offset: ")? {
+                        self.goto(&value);
+                    }
+                }
+                Key::Char(kind @ ('/' | '?')) => {
+                    let label = if kind == '/' {
+                        "search: "
+                    } else {
+                        "search back: "
+                    };
+                    if let Some(value) = self.prompt(terminal, label)? {
+                        match search::parse_pattern(&value, false) {
+                            Ok(pattern) => {
+                                self.search = Some(pattern);
+                                self.find(terminal, kind == '/')?;
+                            }
+                            Err(e) => self.message = e,
+                        }
+                    }
+                }
+                Key::Char('n') => self.find(terminal, true)?,
+                Key::Char('N') => self.find(terminal, false)?,
+                Key::Char('m') => self.toggle_bookmark(),
+                Key::Char(']') => self.jump_bookmark(true),
+                Key::Char('[') => self.jump_bookmark(false),
+                _ => {}
+            }
+        }
+    }
+
+    /// Rows of bytes that fit on screen
+    fn rows(&self, terminal: &Terminal) -> usize {
+        terminal.size().0.saturating_sub(CHROME_LINES).max(1)
+    }
+
+    fn last_offset(&self) -> u64 {
+        self.size.saturating_sub(1)
+    }
+
+    fn move_cursor(&mut self, delta: i64) {
+        let target = self.cursor as i64 + delta;
+        if target < 0 {
+            self.cursor = 0;
+        } else {
+            self.cursor = (target as u64).min(self.last_offset());
+        }
+    }
+
+    fn scroll_to_cursor(&mut self, rows: usize) {
+        let row_start = self.cursor - self.cursor % ROW_SIZE as u64;
+        let screen = (rows * ROW_SIZE) as u64;
+        if row_start < self.top {
+            self.top = row_start;
+        } else if row_start >= self.top + screen {
+            self.top = row_start + ROW_SIZE as u64 - screen;
+        }
+    }
+
+    /// Bytes at offset, shorter at the end of the file
+    fn read(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let len = (self.size.saturating_sub(offset)).min(len as u64) as usize;
+        let mut data = vec![0; len];
+        let mut filled = 0;
+        while filled < len {
+            match self
+                .file
+                .read_at(&mut data[filled..], offset + filled as u64)?
+            {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        data.truncate(filled);
+        Ok(data)
+    }
+
+    fn goto(&mut self, value: &str) {
+        match size::parse_offset(value) {
+            Ok(offset) => {
+                let offset = offset.resolve(self.size);
+                if offset >= self.size && self.size > 0 {
+                    self.message = format!("offset {:#x} is past the end of the file", offset);
+                } else {
+                    self.cursor = offset;
+                }
+            }
+            Err(e) => self.message = e,
+        }
+    }
+
+    /// Move the cursor to the next match after it, or the previous one before it
+    fn find(&mut self, terminal: &mut Terminal, forward: bool) -> io::Result<()> {
+        let pattern = match &self.search {
+            Some(pattern) => pattern.clone(),
+            None => {
+                self.message = "no previous search".to_string();
+                return Ok(());
+            }
+        };
+        self.draw(terminal, "searching...")?;
+        let found = if forward {
+            self.find_forward(&pattern, self.cursor + 1)?
+        } else {
+            self.find_backward(&pattern, self.cursor)?
+        };
+        match found {
+            Some(offset) => {
+                self.cursor = offset;
+                self.message = format!("match at {:#x} ({})", offset, offset);
+            }
+            None if forward => self.message = "no match after the cursor".to_string(),
+            None => self.message = "no match before the cursor".to_string(),
+        }
+        Ok(())
+    }
+
+    /// First match starting at or after from
+    fn find_forward(&self, pattern: &Pattern, from: u64) -> io::Result<Option<u64>> {
+        let overlap = pattern.len() - 1;
+        let mut start = from;
+        while start + pattern.len() as u64 <= self.size {
+            let data = self.read(start, SEARCH_CHUNK_SIZE + overlap)?;
+            let candidates = data.len().saturating_sub(overlap);
+            if let Some(idx) =
+                (0..candidates).find(|idx| search::matches_at(pattern, &data[*idx..]))
+            {
+                return Ok(Some(start + idx as u64));
+            }
+            start += SEARCH_CHUNK_SIZE as u64;
+        }
+        Ok(None)
+    }
+
+    /// Last match starting before end
+    fn find_backward(&self, pattern: &Pattern, end: u64) -> io::Result<Option<u64>> {
+        let overlap = pattern.len() - 1;
+        let mut end = end;
+        while end > 0 {
+            let start = end.saturating_sub(SEARCH_CHUNK_SIZE as u64);
+            let data = self.read(start, (end - start) as usize + overlap)?;
+            let candidates = (end - start) as usize;
+            if let Some(idx) = (0..candidates)
+                .rev()
+                .find(|idx| search::matches_at(pattern, &data[*idx..]))
+            {
+                return Ok(Some(start + idx as u64));
+            }
+            end = start;
+        }
+        Ok(None)
+    }
+
+    fn toggle_bookmark(&mut self) {
+        if self.bookmarks.remove(&self.cursor) {
+            self.message = format!("bookmark removed at {:#x}", self.cursor);
+        } else {
+            self.bookmarks.insert(self.cursor);
+            self.message = format!(
+                "bookmark set at {:#x} ({} bookmarks)",
+                self.cursor,
+                self.bookmarks.len()
+            );
+        }
+    }
+
+    fn jump_bookmark(&mut self, forward: bool) {
+        let target = if forward {
+            self.bookmarks.range(self.cursor + 1..).next()
+        } else {
+            self.bookmarks.range(..self.cursor).next_back()
+        };
+        match target {
+            Some(offset) => {
+                self.cursor = *offset;
+                self.message = format!("bookmark at {:#x}", offset);
+            }
+            None if self.bookmarks.is_empty() => {
+                self.message = "no bookmarks, set one with m".to_string()
+            }
+            None => self.message = "no more bookmarks in that direction".to_string(),
+        }
+    }
+
+    /// Read a line in the status line, None when cancelled with escape,
+    /// Ctrl-C or Ctrl-D
+    fn prompt(&mut self, terminal: &mut Terminal, label: &str) -> io::Result<Option<String>> {
+        let mut value = String::new();
+        loop {
+            self.draw(terminal, &format!("{}{}_", label, value))?;
+            match terminal.read_key()? {
+                Key::Enter => return Ok(Some(value)),
+                Key::Escape | Key::Quit => return Ok(None),
+                Key::Backspace => {
+                    value.pop();
+                }
+                Key::Char(c) => value.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&self, terminal: &mut Terminal, status: &str) -> io::Result<()> {
+        let (height, width) = terminal.size();
+        let rows = self.rows(terminal);
+        let data = self.read(self.top, rows * ROW_SIZE)?;
+
+        let mut screen = String::from("\x1b[H");
+        let percent = ((self.cursor + 1) * 100)
+            .checked_div(self.size)
+            .unwrap_or(100);
+        let title = format!(
+            " {}  {} bytes  cursor {:#x} ({})  {}%  {} bookmarks",
+            self.name,
+            self.size,
+            self.cursor,
+            self.cursor,
+            percent,
+            self.bookmarks.len()
+        );
+        let _ = write!(
+            screen,
+            "{}{}{}\x1b[K\r\n",
+            REVERSE,
+            fit(&title, width),
+            RESET
+        );
+
+        for row in 0..rows {
+            let start = row * ROW_SIZE;
+            if start < data.len() {
+                let end = (start + ROW_SIZE).min(data.len());
+                self.draw_row(&mut screen, self.top + start as u64, &data[start..end]);
+            } else if row == 0 {
+                screen.push_str("(empty file)");
+            }
+            screen.push_str("\x1b[K\r\n");
+        }
+
+        let value = self.read(self.cursor, 8)?;
+        for big_endian in [false, true] {
+            screen.push_str(&fit(&decode_line(&value, big_endian), width));
+            screen.push_str("\x1b[K\r\n");
+        }
+        screen.push_str(&fit(status, width));
+        screen.push_str("\x1b[K");
+        // the status line is the last one, clear anything below after a resize
+        if height > rows + CHROME_LINES {
+            screen.push_str("\x1b[J");
+        }
+        terminal.write_all(screen.as_bytes())
+    }
+
+    /// Canonical style row with the cursor and bookmarks highlighted
+    fn draw_row(&self, screen: &mut String, offset: u64, bytes: &[u8]) {
+        let _ = write!(screen, "{:0width$x}  ", offset, width = self.offset_width);
+        for idx in 0..ROW_SIZE {
+            match bytes.get(idx) {
+                Some(byte) => {
+                    let style = self.style(offset + idx as u64);
+                    let _ = write!(screen, "{}{:02x}{} ", style, byte, reset(style));
+                }
+                None => screen.push_str("   "),
+            }
+            if idx == 7 {
+                screen.push(' ');
+            }
+        }
+        screen.push_str(" |");
+        for (idx, byte) in bytes.iter().enumerate() {
+            let style = self.style(offset + idx as u64);
+            let c = if is_printable(*byte) {
+                *byte as char
+            } else {
+                '.'
+            };
+            let _ = write!(screen, "{}{}{}", style, c, reset(style));
+        }
+        screen.push('|');
+    }
+
+    fn style(&self, offset: u64) -> &'static str {
+        if offset == self.cursor {
+            REVERSE
+        } else if self.bookmarks.contains(&offset) {
+            BOOKMARK
+        } else {
+            ""
+        }
+    }
+}
+
+fn reset(style: &str) -> &'static str {
+    if style.is_empty() {
+        ""
+    } else {
+        RESET
+    }
+}
+
+/// Cut text to the terminal width
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// Values starting at the cursor, "-" for the ones running past the end
+fn decode_line(bytes: &[u8], big_endian: bool) -> String {
+    fn take<const N: usize>(bytes: &[u8], big_endian: bool) -> Option<[u8; N]> {
+        let mut value: [u8; N] = bytes.get(..N)?.try_into().ok()?;
+        if !big_endian {
+            value.reverse();
+        }
+        Some(value)
+    }
+    let show = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+    let u8_value = show(bytes.first().map(|byte| byte.to_string()));
+    let i16_value = show(take::<2>(bytes, big_endian).map(|v| i16::from_be_bytes(v).to_string()));
+    let u32_value = show(take::<4>(bytes, big_endian).map(|v| u32::from_be_bytes(v).to_string()));
+    let f32_value = show(take::<4>(bytes, big_endian).map(|v| format_float(f32::from_be_bytes(v))));
+    let f64_value = show(take::<8>(bytes, big_endian).map(|v| format_float(f64::from_be_bytes(v))));
+    format!(
+        " {}  u8 {:<4} i16 {:<7} u32 {:<11} f32 {:<15} f64 {}",
+        if big_endian { "BE" } else { "LE" },
+        u8_value,
+        i16_value,
+        u32_value,
+        f32_value,
+        f64_value
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, OpenOptions};
+
+    /// Viewer over data, the file is unlinked right away and lives as long
+    /// as the viewer keeps it open
+    fn viewer(test: &str, data: &[u8]) -> Viewer {
+        let path = std::env::temp_dir().join(format!(
+            "myhexdump-viewer-{}-{}.bin",
+            test,
+            std::process::id()
+        ));
+        fs::write(&path, data).unwrap();
+        let file = OpenOptions::new().read(true).open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        Viewer {
+            file,
+            name: test.to_string(),
+            size: data.len() as u64,
+            offset_width: 8,
+            top: 0,
+            cursor: 0,
+            bookmarks: BTreeSet::new(),
+            search: None,
+            message: String::new(),
+        }
+    }
+
+    fn pattern(value: &str) -> Pattern {
+        search::parse_pattern(value, false).unwrap()
+    }
+
+    #[test]
+    fn test_decode_line() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0xf0, 0x3f];
+        assert_eq!(
+            decode_line(&bytes, false),
+            " LE  u8 1    i16 513     u32 67305985    f32 1.539990e-36    f64 1.000000e0"
+        );
+        assert_eq!(
+            decode_line(&bytes[..4], true),
+            " BE  u8 1    i16 258     u32 16909060    f32 2.387939e-38    f64 -"
+        );
+        assert_eq!(
+            decode_line(&[0xff], false),
+            " LE  u8 255  i16 -       u32 -           f32 -               f64 -"
+        );
+        assert_eq!(
+            decode_line(&[], true),
+            " BE  u8 -    i16 -       u32 -           f32 -               f64 -"
+        );
+    }
+
+    #[test]
+    fn test_find_in_small_file() {
+        let viewer = viewer("small", b"abcabcab");
+        let abc = pattern("\"abc\"");
+        assert_eq!(viewer.find_forward(&abc, 0).unwrap(), Some(0));
+        assert_eq!(viewer.find_forward(&abc, 1).unwrap(), Some(3));
+        assert_eq!(viewer.find_forward(&abc, 4).unwrap(), None);
+        assert_eq!(viewer.find_backward(&abc, 8).unwrap(), Some(3));
+        assert_eq!(viewer.find_backward(&abc, 3).unwrap(), Some(0));
+        assert_eq!(viewer.find_backward(&abc, 0).unwrap(), None);
+        // wildcards and a match ending on the last byte
+        assert_eq!(
+            viewer.find_forward(&pattern("62 ?? 61"), 2).unwrap(),
+            Some(4)
+        );
+        assert_eq!(viewer.find_forward(&pattern("\"cab\""), 6).unwrap(), None);
+        assert_eq!(
+            viewer.find_forward(&pattern("\"cab\""), 5).unwrap(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_find_across_chunks() {
+        // matches straddling the chunk boundaries are only found through
+        // the overlap read past each chunk
+        let mut data = vec![0; 2 * SEARCH_CHUNK_SIZE + 16];
+        let first = SEARCH_CHUNK_SIZE - 2;
+        let second = 2 * SEARCH_CHUNK_SIZE - 1;
+        data[first..first + 4].copy_from_slice(b"\xde\xad\xbe\xef");
+        data[second..second + 4].copy_from_slice(b"\xde\xad\xbe\xef");
+        let viewer = viewer("chunks", &data);
+        let needle = pattern("de ad be ef");
+
+        assert_eq!(viewer.find_forward(&needle, 0).unwrap(), Some(first as u64));
+        assert_eq!(
+            viewer.find_forward(&needle, first as u64 + 1).unwrap(),
+            Some(second as u64)
+        );
+        assert_eq!(
+            viewer.find_forward(&needle, second as u64 + 1).unwrap(),
+            None
+        );
+
+        let end = data.len() as u64;
+        assert_eq!(
+            viewer.find_backward(&needle, end).unwrap(),
+            Some(second as u64)
+        );
+        assert_eq!(
+            viewer.find_backward(&needle, second as u64).unwrap(),
+            Some(first as u64)
+        );
+        assert_eq!(viewer.find_backward(&needle, first as u64).unwrap(), None);
+    }
+}
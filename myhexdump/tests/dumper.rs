@@ -0,0 +1,177 @@
+//! Snapshot tests of the library Dumper: dumps are written to a Vec and
+//! compared with tests/golden/<name>.txt. Run with UPDATE_GOLDEN=1 to rewrite
+//! the snapshots after an intended output change.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use myhexdump::input::CHUNK_SIZE;
+use myhexdump::{Dumper, Error, Input, InputStream, Layout, ReadInput, RowFormat};
+
+const LAYOUTS: [Layout; 4] = [
+    Layout::Default,
+    Layout::Canonical,
+    Layout::Xxd,
+    Layout::Hex16,
+];
+
+fn data_file(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name);
+    path.to_string_lossy().into_owned()
+}
+
+fn check_snapshot(name: &str, actual: &[u8]) {
+    let actual = String::from_utf8(actual.to_vec()).unwrap();
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("missing snapshot {}: {}", path.display(), e));
+    assert_eq!(actual, expected, "dump {} changed", name);
+}
+
+fn dump<I: Input>(
+    input: &mut I,
+    layout: Layout,
+    offset: u64,
+    length: Option<u64>,
+) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    Dumper::new(&mut out, RowFormat::new(layout))
+        .with_offset(offset)
+        .with_length(length)
+        .dump(input)?;
+    Ok(out)
+}
+
+/// Hands out at most size bytes per read, like a pipe
+struct ShortReads<'a> {
+    data: &'a [u8],
+    size: usize,
+}
+
+impl Read for ShortReads<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.size.min(buf.len()).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        Ok(len)
+    }
+}
+
+/// Zeros with a few marked bytes around each chunk boundary, so the dump
+/// squeezes to a handful of rows
+fn chunk_boundary_data() -> Vec<u8> {
+    let mut data = vec![0u8; 2 * CHUNK_SIZE + 100];
+    for boundary in [CHUNK_SIZE, 2 * CHUNK_SIZE] {
+        for (idx, byte) in data[boundary - 9..boundary + 9].iter_mut().enumerate() {
+            *byte = 0x41 + idx as u8;
+        }
+    }
+    data
+}
+
+#[test]
+fn partial_rows() {
+    let data = fs::read(data_file("long.bin")).unwrap();
+    let mut all = Vec::new();
+    for layout in LAYOUTS {
+        // pieces of 7 bytes never line up with the rows
+        let mut input = ReadInput::new(ShortReads {
+            data: &data,
+            size: 7,
+        });
+        all.extend(dump(&mut input, layout, 3, Some(37)).unwrap());
+        all.extend_from_slice(b"--\n");
+    }
+    check_snapshot("lib_partial_rows", &all);
+}
+
+#[test]
+fn length_across_chunk_boundaries() {
+    let data = chunk_boundary_data();
+    let offset = 5;
+    let length = Some(2 * CHUNK_SIZE as u64 + 3 - offset);
+    let mut input = ReadInput::new(ShortReads {
+        data: &data,
+        size: 4093,
+    });
+    let out = dump(&mut input, Layout::Canonical, offset, length).unwrap();
+    check_snapshot("lib_length_chunks", &out);
+
+    // mapped files are handed out in chunks of CHUNK_SIZE
+    let path = std::env::temp_dir().join(format!("myhexdump-chunks-{}.bin", std::process::id()));
+    fs::write(&path, &data).unwrap();
+    let mut input = InputStream::new(vec![path.to_string_lossy().into_owned()]);
+    let mapped = dump(&mut input, Layout::Canonical, offset, length).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(
+        String::from_utf8(mapped).unwrap(),
+        String::from_utf8(out).unwrap()
+    );
+}
+
+#[test]
+fn empty_input() {
+    for layout in LAYOUTS {
+        let out = dump(&mut ReadInput::new(io::empty()), layout, 0, None).unwrap();
+        assert!(out.is_empty(), "{:?}", layout);
+        let mut input = InputStream::new(vec![data_file("empty.bin")]);
+        let out = dump(&mut input, layout, 0, Some(16)).unwrap();
+        assert!(out.is_empty(), "{:?}", layout);
+        assert!(!input.had_error());
+    }
+}
+
+#[test]
+fn skip_to_end_of_input() {
+    let size = fs::metadata(data_file("a.bin")).unwrap().len();
+    let mut input = InputStream::new(vec![data_file("a.bin")]);
+    let out = dump(&mut input, Layout::Canonical, size, None).unwrap();
+    // nothing left to dump, not even the end offset
+    assert!(out.is_empty());
+    assert!(!input.had_error());
+
+    let mut input = ReadInput::new(&b"0123456789"[..]);
+    let out = dump(&mut input, Layout::Canonical, 9, None).unwrap();
+    check_snapshot("lib_skip_to_last_byte", &out);
+}
+
+#[test]
+fn skip_past_end_fails() {
+    let mut input = InputStream::new(vec![data_file("a.bin"), data_file("b.bin")]);
+    match dump(&mut input, Layout::Canonical, 42, None) {
+        Err(Error::SkipPastEnd { offset, size }) => assert_eq!((offset, size), (42, 41)),
+        result => panic!("expected SkipPastEnd, got {:?}", result.map(|_| ())),
+    }
+}
+
+/// Output that fails every write
+struct Closed;
+
+impl Write for Closed {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn output_errors_are_returned() {
+    let mut input = ReadInput::new(&b"some data"[..]);
+    let result = Dumper::new(Closed, RowFormat::new(Layout::Xxd)).dump(&mut input);
+    match result {
+        Err(error @ Error::Output(_)) => assert!(error.is_broken_pipe()),
+        result => panic!("expected an output error, got {:?}", result),
+    }
+}
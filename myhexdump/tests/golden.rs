@@ -0,0 +1,716 @@
+//! Golden-file tests: run the binary on the inputs in tests/data and compare
+//! stdout with tests/golden/<name>.txt. Run with UPDATE_GOLDEN=1 to rewrite
+//! the expected files after an intended output change.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn tests_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_myhexdump"));
+    command.args(args).current_dir(tests_dir().join("data"));
+    command
+}
+
+fn check_output(name: &str, output: Output) {
+    check_output_status(name, output, 0);
+}
+
+fn check_output_status(name: &str, output: Output, code: i32) {
+    assert_eq!(
+        output.status.code(),
+        Some(code),
+        "{} failed: {}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let actual = String::from_utf8(output.stdout).unwrap();
+    let golden = tests_dir().join("golden").join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden)
+        .unwrap_or_else(|e| panic!("missing golden file {}: {}", golden.display(), e));
+    assert_eq!(actual, expected, "output of {} changed", name);
+}
+
+fn check(name: &str, args: &[&str]) {
+    check_output(name, command(args).output().unwrap());
+}
+
+/// Feed stdin in small pieces with pauses, so the dump sees short reads
+fn check_stdin_chunks(name: &str, args: &[&str], data: &[u8], chunk: usize) {
+    let mut child = command(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for piece in data.chunks(chunk) {
+        // with -n the dump can exit before all the data was sent
+        if stdin.write_all(piece).and_then(|_| stdin.flush()).is_err() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+    drop(stdin);
+    check_output(name, child.wait_with_output().unwrap());
+}
+
+#[test]
+fn default_layout_across_files() {
+    check("default_two_files", &["a.bin", "b.bin"]);
+}
+
+#[test]
+fn canonical_across_files() {
+    check("canonical_two_files", &["-C", "a.bin", "b.bin"]);
+}
+
+#[test]
+fn xxd_across_files() {
+    check("xxd_two_files", &["--xxd", "-c", "12", "a.bin", "b.bin"]);
+}
+
+#[test]
+fn stdin_short_reads() {
+    let data = fs::read(tests_dir().join("data/long.bin")).unwrap();
+    check_stdin_chunks("stdin_short_reads", &["-C", "-n", "200"], &data[..300], 7);
+}
+
+#[test]
+fn skip_with_suffix() {
+    check("skip_1k", &["-C", "-s", "1K", "-n", "0x40", "long.bin"]);
+}
+
+#[test]
+fn skip_octal() {
+    check("skip_octal", &["-C", "-s", "010", "-n", "8", "long.bin"]);
+}
+
+#[test]
+fn skip_into_second_file() {
+    check(
+        "skip_across_files",
+        &["-C", "-s", "5", "-n", "20", "a.bin", "b.bin"],
+    );
+}
+
+#[test]
+fn skip_from_end() {
+    check("skip_negative", &["--xxd", "-s", "-20", "a.bin", "b.bin"]);
+}
+
+#[test]
+fn skip_from_end_past_start() {
+    check("skip_negative_clamped", &["-C", "-s", "-1K", "a.bin"]);
+}
+
+#[test]
+fn mmap_matches_reads() {
+    for args in [
+        &["-C"][..],
+        &["--xxd", "-s", "0x3f", "-n", "1000"],
+        &["-s", "-20"],
+    ] {
+        let read = command(&[args, &["long.bin", "a.bin"]].concat())
+            .output()
+            .unwrap();
+        let mapped = command(&[args, &["--mmap", "long.bin", "a.bin"]].concat())
+            .output()
+            .unwrap();
+        assert!(read.status.success() && mapped.status.success());
+        assert_eq!(read.stdout, mapped.stdout, "--mmap changed {:?}", args);
+    }
+}
+
+#[test]
+fn length_zero() {
+    check("length_zero", &["-C", "-n", "0", "long.bin"]);
+}
+
+#[test]
+fn length_not_row_aligned() {
+    check("length_unaligned", &["-n", "21", "long.bin"]);
+}
+
+#[test]
+fn squeeze_identical_rows() {
+    check("squeeze", &["-C", "zeros.bin"]);
+}
+
+#[test]
+fn skip_past_end_fails() {
+    let output = command(&["-C", "-s", "4K", "a.bin"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn invalid_suffix_fails() {
+    let output = command(&["-s", "1Q", "a.bin"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn color_by_byte_class() {
+    check("color_canonical", &["-C", "--color=always", "classes.bin"]);
+    check("color_xxd", &["--xxd", "--color=always", "classes.bin"]);
+}
+
+#[test]
+fn color_auto_off_when_piped() {
+    check("color_auto", &["-C", "--color=auto", "classes.bin"]);
+}
+
+#[test]
+fn diff_two_files() {
+    let output = command(&["--diff", "long.bin", "long_patched.bin", "-n", "0x1a0"])
+        .output()
+        .unwrap();
+    check_output_status("diff", output, 1);
+}
+
+#[test]
+fn diff_only_differences() {
+    let args = [
+        "--diff",
+        "long.bin",
+        "long_patched.bin",
+        "-n",
+        "0x1a0",
+        "--only-diff",
+        "--context",
+        "2",
+    ];
+    check_output_status("diff_only", command(&args).output().unwrap(), 1);
+}
+
+#[test]
+fn diff_identical_files() {
+    let args = ["--diff", "a.bin", "a.bin", "--only-diff"];
+    check_output_status("diff_identical", command(&args).output().unwrap(), 0);
+}
+
+#[test]
+fn diff_skip_past_end_fails() {
+    // long_patched.bin is shorter than the offset, long.bin is not
+    let output = command(&["--diff", "long.bin", "long_patched.bin", "-s", "0x400"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("past the end"), "{}", stderr);
+
+    // the end itself is fine
+    let output = command(&["--diff", "a.bin", "a.bin", "-s", "11"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn template_png() {
+    check("template_png", &["-C", "--template", "png", "tiny.png"]);
+}
+
+#[test]
+fn template_custom() {
+    check(
+        "template_custom",
+        &["-C", "--template", "custom.toml", "custom.bin"],
+    );
+}
+
+#[test]
+fn template_with_skip() {
+    check(
+        "template_skip",
+        &[
+            "-C",
+            "--template",
+            "custom.toml",
+            "-s",
+            "0x10",
+            "-n",
+            "32",
+            "custom.bin",
+        ],
+    );
+}
+
+#[test]
+fn template_wrong_format_fails() {
+    let output = command(&["--template", "elf", "tiny.png"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn find_hex_with_wildcards() {
+    check(
+        "find_wildcard",
+        &[
+            "--find",
+            "de ad ?? ef",
+            "--context",
+            "1",
+            "-C",
+            "custom.bin",
+        ],
+    );
+}
+
+#[test]
+fn find_text_and_utf16() {
+    check("find_text", &["--find", "\"hex\"", "a.bin", "b.bin"]);
+    let args = [
+        "--find",
+        "\"world\"",
+        "--utf16",
+        "--context",
+        "1",
+        "--xxd",
+        "utf16.bin",
+    ];
+    check("find_utf16", &args);
+}
+
+#[test]
+fn find_across_short_reads() {
+    let data = fs::read(tests_dir().join("data/long.bin")).unwrap();
+    let args = ["--find", "8c939aa1", "--context", "1", "-C"];
+    check_stdin_chunks("find_short_reads", &args, &data, 509);
+}
+
+#[test]
+fn find_without_match() {
+    let output = command(&["--find", "\"nothing\"", "long.bin"])
+        .output()
+        .unwrap();
+    check_output_status("find_none", output, 1);
+}
+
+#[test]
+fn strings_ascii_and_utf16() {
+    check("strings", &["--strings", "utf16.bin", "custom.bin"]);
+    check("strings_min", &["--strings=6", "-s", "2", "utf16.bin"]);
+}
+
+#[test]
+fn patch_dry_run() {
+    let args = [
+        "patch",
+        "long.bin",
+        "--at",
+        "0x1f4",
+        "--bytes",
+        "90 ?? 90",
+        "--dry-run",
+        "-C",
+    ];
+    check("patch_dry_run", &args);
+}
+
+#[test]
+fn patch_dry_run_with_format() {
+    let args = [
+        "-e",
+        "\"%08.8_ax  \" 16/1 \"%02x \" \"\\n\"",
+        "patch",
+        "long.bin",
+        "--at",
+        "0x1f4",
+        "--bytes",
+        "90 ?? 90",
+        "--dry-run",
+    ];
+    check("patch_dry_run_format", &args);
+}
+
+#[test]
+fn patch_in_place() {
+    let path = std::env::temp_dir().join(format!("myhexdump-patch-{}.bin", std::process::id()));
+    fs::copy(tests_dir().join("data/a.bin"), &path).unwrap();
+    let file = path.to_str().unwrap();
+
+    // growing the file needs --allow-extend
+    let output = command(&["patch", file, "--at", "-1", "--bytes", "4142"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(fs::metadata(&path).unwrap().len(), 11);
+
+    let output = command(&["patch", file, "--at", "2", "--bytes", "\"XY\"", "--backup"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let mut expected = fs::read(tests_dir().join("data/a.bin")).unwrap();
+    expected[2..4].copy_from_slice(b"XY");
+    assert_eq!(fs::read(&path).unwrap(), expected);
+
+    let backup = format!("{}.bak", file);
+    assert_eq!(
+        fs::read(&backup).unwrap(),
+        fs::read(tests_dir().join("data/a.bin")).unwrap()
+    );
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
+
+/// Revert dump (fed through stdin) with the extra args, returning the output
+fn revert(dump: &[u8], args: &[&str]) -> Output {
+    let mut child = command(&[&["-r"], args].concat())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(dump).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Dump file with dump_args, revert it and compare with the original bytes
+fn check_round_trip(file: &str, dump_args: &[&str], revert_args: &[&str]) {
+    let dump = command(&[dump_args, &[file]].concat()).output().unwrap();
+    assert!(dump.status.success(), "dump {:?} failed", dump_args);
+    let output = revert(&dump.stdout, revert_args);
+    assert!(
+        output.status.success(),
+        "revert of {:?} failed: {}",
+        dump_args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let expected = fs::read(tests_dir().join("data").join(file)).unwrap();
+    assert!(
+        output.stdout == expected,
+        "revert of {:?} differs from {}",
+        dump_args,
+        file
+    );
+}
+
+#[test]
+fn revert_default_and_canonical() {
+    check_round_trip("long.bin", &["-v"], &[]);
+    check_round_trip("long.bin", &["-C", "-v"], &[]);
+    check_round_trip("a.bin", &["-C"], &[]);
+}
+
+#[test]
+fn revert_xxd_groups() {
+    for group in ["0", "1", "2", "4", "8"] {
+        check_round_trip("long.bin", &["--xxd", "-g", group], &[]);
+        check_round_trip("b.bin", &["--xxd", "-c", "12", "-g", group], &[]);
+    }
+}
+
+#[test]
+fn revert_plain() {
+    check_round_trip("long.bin", &["-p"], &["-p"]);
+    check_round_trip("a.bin", &["-p", "-c", "5"], &["-p"]);
+}
+
+#[test]
+fn revert_squeezed_rows() {
+    // zeros.bin ends with '*' and the final offset line
+    check_round_trip("zeros.bin", &["-C"], &[]);
+    check_round_trip("zeros.bin", &["--xxd"], &[]);
+}
+
+#[test]
+fn revert_extends_to_final_offset() {
+    let output = revert(b"00000000  41 42  |AB|\n00000010\n", &[]);
+    assert!(output.status.success());
+    let mut expected = b"AB".to_vec();
+    expected.resize(0x10, 0);
+    assert_eq!(output.stdout, expected);
+}
+
+#[test]
+fn revert_backwards_offset() {
+    let dump = b"00000010  41  |A|\n00000000  42  |B|\n";
+    let output = revert(dump, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("goes backwards"), "{}", stderr);
+
+    // a file can be seeked back to
+    let path =
+        std::env::temp_dir().join(format!("myhexdump-revert-back-{}.bin", std::process::id()));
+    let output = revert(dump, &["--output", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let mut expected = vec![0; 0x11];
+    expected[0] = b'B';
+    expected[0x10] = b'A';
+    assert_eq!(fs::read(&path).unwrap(), expected);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn revert_sparse_output() {
+    let path = std::env::temp_dir().join(format!(
+        "myhexdump-revert-sparse-{}.bin",
+        std::process::id()
+    ));
+    let dump = b"00000000  41  |A|\n00100000  42 43  |BC|\n00200000\n";
+    let output = revert(dump, &["--output", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let data = fs::read(&path).unwrap();
+    assert_eq!(data.len(), 0x200000);
+    assert_eq!(data[0], b'A');
+    assert_eq!(&data[0x100000..0x100002], b"BC");
+    assert_eq!(data.iter().filter(|&&byte| byte != 0).count(), 3);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn revert_odd_nibble_fails() {
+    let output = revert(b"4142\n4\n", &["-p"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("odd number of hex digits"), "{}", stderr);
+    // the complete bytes are still written
+    assert_eq!(output.stdout, b"AB");
+}
+
+#[test]
+fn custom_formats() {
+    let args = [
+        "-e",
+        r#""%08.8_ax " 4/4 "%08x " "\n""#,
+        "-e",
+        r#""         " 4/4 "%10d " "\n""#,
+        "-e",
+        r#""         " 16/1 "%3_c" "\n""#,
+        "-n",
+        "40",
+        "utf16.bin",
+    ];
+    check("format_custom", &args);
+    let args = [
+        "-e",
+        r#"1/4 "%f " 1/8 "%+.3e " 1/4 "%G" "\n""#,
+        "-n",
+        "32",
+        "long.bin",
+    ];
+    check("format_float", &args);
+}
+
+/// The built-in layouts must print exactly what their -e formats print
+#[test]
+fn layouts_match_their_formats() {
+    for layout in ["", "-C", "-x", "-o", "-d"] {
+        let mut show = vec!["--show-format"];
+        if !layout.is_empty() {
+            show.push(layout);
+        }
+        let output = command(&show).output().unwrap();
+        let formats: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                line.strip_prefix("-e '")
+                    .unwrap()
+                    .strip_suffix('\'')
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        for file in ["a.bin", "long.bin", "classes.bin"] {
+            let mut args: Vec<&str> = formats
+                .iter()
+                .flat_map(|format| ["-e", format.as_str()])
+                .collect();
+            args.push(file);
+            let custom = command(&args).output().unwrap().stdout;
+            let builtin = command(
+                &[layout, file]
+                    .into_iter()
+                    .filter(|arg| !arg.is_empty())
+                    .collect::<Vec<_>>(),
+            )
+            .output()
+            .unwrap()
+            .stdout;
+            let builtin = String::from_utf8(builtin).unwrap();
+            // the default layout starts with a header the formats do not print
+            let builtin: String = if layout.is_empty() {
+                builtin.split_inclusive('\n').skip(4).collect()
+            } else {
+                builtin
+            };
+            assert_eq!(
+                String::from_utf8(custom).unwrap(),
+                builtin,
+                "{} {}",
+                layout,
+                file
+            );
+        }
+    }
+}
+
+#[test]
+fn stats_histogram_and_entropy() {
+    check(
+        "stats",
+        &[
+            "--stats",
+            "--block-size",
+            "0x100",
+            "-s",
+            "0x80",
+            "-n",
+            "1K",
+            "custom.bin",
+            "long.bin",
+        ],
+    );
+}
+
+#[test]
+fn emit_source_arrays() {
+    check(
+        "emit_c",
+        &["--emit", "c", "-s", "4", "-n", "30", "long.bin"],
+    );
+    check(
+        "emit_rust",
+        &["--emit", "rust", "--name", "fixture", "a.bin"],
+    );
+    check(
+        "emit_python",
+        &["--emit", "python", "-n", "20", "utf16.bin"],
+    );
+    check(
+        "emit_base64",
+        &["--emit", "base64", "-n", "100", "long.bin"],
+    );
+}
+
+#[test]
+fn emit_empty_input() {
+    let output = command(&["--emit", "c", "empty.bin"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let output = command(&["--emit", "c", "-s", "11", "a.bin"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    // the other languages have empty literals
+    let output = command(&["--emit", "python", "empty.bin"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"empty_bin = b\"\"\n");
+}
+
+#[test]
+fn emit_invalid_name_fails() {
+    for name in ["", "1st", "my-data", "a b", "tab\u{e9}"] {
+        let output = command(&["--emit", "c", "--name", name, "a.bin"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2), "--name {:?}", name);
+        assert!(output.stdout.is_empty());
+    }
+    let output = command(&["--emit", "c", "--name", "_data2", "a.bin"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn decompress_matches_plain_dump() {
+    let plain = command(&["-C", "-s", "0x20", "-n", "0x400", "long.bin"])
+        .output()
+        .unwrap()
+        .stdout;
+    for file in ["long.bin.gz", "long.bin.zst", "long.bin.xz", "long.bin.bz2"] {
+        let output = command(&["-z", "-C", "-s", "0x20", "-n", "0x400", file])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", file);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(plain.clone()).unwrap(),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn decompress_stdin_with_compressed_offsets() {
+    let data = fs::read(tests_dir().join("data/long.bin.gz")).unwrap();
+    let args = ["-z", "-C", "--compressed-offsets", "-n", "0x60"];
+    check_stdin_chunks("decompress_offsets", &args, &data, 100);
+    check(
+        "decompress_uncompressed",
+        &[
+            "-z",
+            "--compressed-offsets",
+            "--xxd",
+            "a.bin",
+            "long.bin.xz",
+            "-n",
+            "0x30",
+        ],
+    );
+}
+
+#[test]
+fn grouped_words() {
+    check(
+        "group_little_unsigned",
+        &[
+            "-C", "--group", "4", "--decode", "unsigned", "-n", "0x25", "long.bin",
+        ],
+    );
+    check(
+        "group_big_signed",
+        &[
+            "--group", "2", "--endian", "big", "--decode", "signed", "-n", "0x1b", "long.bin",
+        ],
+    );
+    check(
+        "group_float",
+        &[
+            "-C", "--group", "8", "--decode", "float", "-s", "3", "-n", "0x30", "long.bin",
+        ],
+    );
+}
+
+/// Single byte words are the plain layouts
+#[test]
+fn group_of_one_byte() {
+    for layout in ["-C", "-v"] {
+        let plain = command(&[layout, "long.bin"]).output().unwrap().stdout;
+        let grouped = command(&[layout, "--group", "1", "long.bin"])
+            .output()
+            .unwrap()
+            .stdout;
+        assert_eq!(grouped, plain, "{}", layout);
+    }
+}
+
+#[test]
+fn float_needs_wide_words() {
+    let output = command(&["--group", "2", "--decode", "float", "a.bin"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+}
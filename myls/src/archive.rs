@@ -0,0 +1,432 @@
+use chrono::{Local, TimeZone};
+use flate2::read::GzDecoder;
+use libc::{S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFLNK, S_IFMT, S_IFREG};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tar::EntryType;
+
+const ARCHIVE_EXTENSIONS: [&str; 4] = [".tar", ".tar.gz", ".tgz", ".zip"];
+
+#[derive(Debug, Clone)]
+pub struct ArchiveMember {
+    /// Path inside the archive, without leading "./" or trailing '/'
+    pub path: String,
+    /// Full st_mode style value, including the file type bits
+    pub mode: u32,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub size: u64,
+    pub last_modified: SystemTime,
+    pub symlink_path: String,
+}
+
+impl ArchiveMember {
+    /// Directory entry for a path that only shows up as a parent of other members
+    fn implicit_dir(path: &str, last_modified: SystemTime) -> ArchiveMember {
+        ArchiveMember {
+            path: path.to_string(),
+            mode: S_IFDIR | 0o755,
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            size: 0,
+            last_modified,
+            symlink_path: String::new(),
+        }
+    }
+
+    pub fn filename(&self) -> &str {
+        match self.path.rsplit_once('/') {
+            Some((_, name)) => name,
+            None => &self.path,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+}
+
+fn is_archive_name(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    ARCHIVE_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
+}
+
+/// Check if a command line path refers to an archive, either directly
+/// (archive.tar.gz) or to a directory inside it (archive.zip:sub/dir).
+/// Returns the archive path and the directory inside the archive.
+pub fn split_archive_path(path: &str) -> Option<(String, String)> {
+    if is_archive_name(path) && Path::new(path).is_file() {
+        return Some((path.to_string(), String::new()));
+    }
+
+    for (idx, _) in path.match_indices(':') {
+        let archive = &path[..idx];
+        if is_archive_name(archive) && Path::new(archive).is_file() {
+            let inner = path[idx + 1..].trim_matches('/').to_string();
+            return Some((archive.to_string(), inner));
+        }
+    }
+    None
+}
+
+/// Build the path used to refer to a directory inside an archive
+pub fn join_archive_path(archive: &str, inner: &str, name: &str) -> String {
+    if inner.is_empty() {
+        format!("{}:{}", archive, name)
+    } else {
+        format!("{}:{}/{}", archive, inner, name)
+    }
+}
+
+fn normalize_member_path(path: &str) -> String {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    path.trim_matches('/').to_string()
+}
+
+/// Read the headers of every member in the archive
+pub fn read_members(archive: &Path) -> Result<Vec<ArchiveMember>, String> {
+    let file = match File::open(archive) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Failed to open archive {:?}: {}", archive, e)),
+    };
+
+    let name = archive.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        read_zip_members(file)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        read_tar_members(GzDecoder::new(BufReader::new(file)))
+    } else {
+        read_tar_members(BufReader::new(file))
+    }
+}
+
+fn read_tar_members<R: Read>(reader: R) -> Result<Vec<ArchiveMember>, String> {
+    let mut archive = tar::Archive::new(reader);
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(e) => return Err(format!("Failed to read tar entries: {}", e)),
+    };
+
+    let mut members: Vec<ArchiveMember> = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => return Err(format!("Failed to read tar entry: {}", e)),
+        };
+        let header = entry.header();
+
+        let path = match entry.path() {
+            Ok(path) => normalize_member_path(&path.to_string_lossy()),
+            Err(e) => {
+                eprintln!("Failed to read tar entry path: {}", e);
+                continue;
+            }
+        };
+        if path.is_empty() {
+            // the "./" entry itself
+            continue;
+        }
+
+        let file_type = match header.entry_type() {
+            EntryType::Directory => S_IFDIR,
+            EntryType::Symlink => S_IFLNK,
+            EntryType::Char => S_IFCHR,
+            EntryType::Block => S_IFBLK,
+            EntryType::Fifo => S_IFIFO,
+            _ => S_IFREG,
+        };
+        let permissions = header.mode().unwrap_or(0o644) & 0o7777;
+
+        let symlink_path = match entry.link_name() {
+            Ok(Some(link)) if file_type == S_IFLNK => link.to_string_lossy().into_owned(),
+            _ => String::new(),
+        };
+
+        let mtime = header.mtime().unwrap_or(0);
+        members.push(ArchiveMember {
+            path,
+            mode: file_type | permissions,
+            uid: header.uid().ok().map(|uid| uid as u32),
+            gid: header.gid().ok().map(|gid| gid as u32),
+            user: header.username().ok().flatten().map(|s| s.to_string()),
+            group: header.groupname().ok().flatten().map(|s| s.to_string()),
+            size: header.size().unwrap_or(0),
+            last_modified: SystemTime::UNIX_EPOCH + Duration::from_secs(mtime),
+            symlink_path,
+        });
+    }
+    Ok(members)
+}
+
+fn read_zip_members(file: File) -> Result<Vec<ArchiveMember>, String> {
+    let mut archive = match zip::ZipArchive::new(BufReader::new(file)) {
+        Ok(archive) => archive,
+        Err(e) => return Err(format!("Failed to read zip archive: {}", e)),
+    };
+
+    let mut members: Vec<ArchiveMember> = Vec::new();
+    for idx in 0..archive.len() {
+        let mut zip_file = match archive.by_index(idx) {
+            Ok(zip_file) => zip_file,
+            Err(e) => {
+                eprintln!("Failed to read zip entry {}: {}", idx, e);
+                continue;
+            }
+        };
+
+        let path = normalize_member_path(zip_file.name());
+        if path.is_empty() {
+            continue;
+        }
+
+        // zip files created on unix keep the full st_mode, otherwise make one up
+        let mode = match zip_file.unix_mode() {
+            Some(mode) if mode & S_IFMT != 0 => mode,
+            Some(mode) if zip_file.is_dir() => S_IFDIR | (mode & 0o7777),
+            Some(mode) => S_IFREG | (mode & 0o7777),
+            None if zip_file.is_dir() => S_IFDIR | 0o755,
+            None => S_IFREG | 0o644,
+        };
+
+        // symlink targets are stored as the member contents
+        let mut symlink_path = String::new();
+        if mode & S_IFMT == S_IFLNK {
+            if let Err(e) = zip_file.read_to_string(&mut symlink_path) {
+                eprintln!("Failed to read symlink target of {}: {}", path, e);
+            }
+        }
+
+        // zip timestamps have no timezone, they are local time by convention
+        let last_modified = match zip_file.last_modified() {
+            Some(time) => Local
+                .with_ymd_and_hms(
+                    time.year() as i32,
+                    time.month() as u32,
+                    time.day() as u32,
+                    time.hour() as u32,
+                    time.minute() as u32,
+                    time.second() as u32,
+                )
+                .single()
+                .map(SystemTime::from)
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+            None => SystemTime::UNIX_EPOCH,
+        };
+
+        members.push(ArchiveMember {
+            path,
+            mode,
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            size: zip_file.size(),
+            last_modified,
+            symlink_path,
+        });
+    }
+    Ok(members)
+}
+
+/// Return the members directly inside dir, synthesizing directory entries
+/// for paths that only appear as parents of other members. When dir names a
+/// file member just that member is returned, the same as listing a file.
+pub fn list_dir(
+    members: &[ArchiveMember],
+    dir: &str,
+    default_modified: SystemTime,
+) -> Result<Vec<ArchiveMember>, String> {
+    if let Some(member) = members.iter().find(|member| member.path == dir) {
+        if !member.is_dir() {
+            return Ok(vec![member.clone()]);
+        }
+    }
+
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir)
+    };
+
+    let mut found_dir = dir.is_empty();
+    let mut children: Vec<ArchiveMember> = Vec::new();
+    for member in members {
+        let relative = match member.path.strip_prefix(&prefix) {
+            Some(relative) => relative,
+            None => continue,
+        };
+        found_dir = true;
+
+        match relative.split_once('/') {
+            Some((child, _)) => {
+                if !children.iter().any(|item| item.filename() == child) {
+                    let path = format!("{}{}", prefix, child);
+                    children.push(ArchiveMember::implicit_dir(&path, default_modified));
+                }
+            }
+            None => {
+                // explicit entries win over synthesized directories
+                children.retain(|item| item.filename() != relative);
+                children.push(member.clone());
+            }
+        }
+    }
+
+    if !found_dir && !members.iter().any(|member| member.path == dir) {
+        return Err(format!("No such file or directory in archive: {}", dir));
+    }
+    Ok(children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::S_IFREG;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Empty file named name in a fresh temporary directory, the directory
+    /// is removed again on drop
+    struct TempFile {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl TempFile {
+        fn new(test: &str, name: &str) -> TempFile {
+            let dir = std::env::temp_dir().join(format!("myls-{}-{}", test, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join(name);
+            fs::write(&path, b"").unwrap();
+            TempFile { dir, path }
+        }
+
+        fn path(&self) -> &str {
+            self.path.to_str().unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn member(path: &str, mode: u32) -> ArchiveMember {
+        ArchiveMember {
+            path: path.to_string(),
+            mode,
+            uid: None,
+            gid: None,
+            user: None,
+            group: None,
+            size: 1,
+            last_modified: SystemTime::UNIX_EPOCH,
+            symlink_path: String::new(),
+        }
+    }
+
+    fn names(members: &[ArchiveMember]) -> Vec<&str> {
+        members.iter().map(|member| member.filename()).collect()
+    }
+
+    #[test]
+    fn test_split_archive_itself() {
+        let file = TempFile::new("split-itself", "data.tar.gz");
+        let path = file.path();
+        assert_eq!(
+            split_archive_path(path),
+            Some((path.to_string(), String::new()))
+        );
+    }
+
+    #[test]
+    fn test_split_inner_dir() {
+        let file = TempFile::new("split-inner", "Data.ZIP");
+        let path = file.path();
+        assert_eq!(
+            split_archive_path(&format!("{}:sub/dir/", path)),
+            Some((path.to_string(), "sub/dir".to_string()))
+        );
+        assert_eq!(
+            split_archive_path(&format!("{}:/", path)),
+            Some((path.to_string(), String::new()))
+        );
+    }
+
+    #[test]
+    fn test_split_not_archive() {
+        let file = TempFile::new("split-not", "notes.txt");
+        let path = file.path();
+        assert_eq!(split_archive_path(path), None);
+        assert_eq!(split_archive_path(&format!("{}:sub", path)), None);
+        // archive names that are not files on disk
+        assert_eq!(split_archive_path("/nonexistent/data.tar:sub"), None);
+    }
+
+    #[test]
+    fn test_join_archive_path() {
+        assert_eq!(join_archive_path("a.tar", "", "sub"), "a.tar:sub");
+        assert_eq!(join_archive_path("a.tar", "sub", "dir"), "a.tar:sub/dir");
+    }
+
+    #[test]
+    fn test_normalize_member_path() {
+        assert_eq!(normalize_member_path("./sub/dir/"), "sub/dir");
+        assert_eq!(normalize_member_path("file"), "file");
+        assert_eq!(normalize_member_path("./"), "");
+    }
+
+    #[test]
+    fn test_list_dir_implicit_dirs() {
+        let members = vec![
+            member("top.txt", S_IFREG | 0o644),
+            member("sub/inner/deep.txt", S_IFREG | 0o644),
+            member("sub/file.txt", S_IFREG | 0o644),
+        ];
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+
+        let root = list_dir(&members, "", modified).unwrap();
+        assert_eq!(names(&root), ["top.txt", "sub"]);
+        assert!(root[1].is_dir());
+        assert_eq!(root[1].path, "sub");
+        assert_eq!(root[1].last_modified, modified);
+
+        let sub = list_dir(&members, "sub", modified).unwrap();
+        assert_eq!(names(&sub), ["inner", "file.txt"]);
+        assert!(sub[0].is_dir());
+        assert_eq!(sub[0].path, "sub/inner");
+    }
+
+    #[test]
+    fn test_list_dir_explicit_dir_wins() {
+        let members = vec![
+            member("sub/file.txt", S_IFREG | 0o644),
+            member("sub", S_IFDIR | 0o700),
+        ];
+        let root = list_dir(&members, "", SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(root.len(), 1);
+        assert_eq!(root[0].mode, S_IFDIR | 0o700);
+    }
+
+    #[test]
+    fn test_list_dir_file_member() {
+        let members = vec![member("sub/file.txt", S_IFREG | 0o644)];
+        let listed = list_dir(&members, "sub/file.txt", SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(names(&listed), ["file.txt"]);
+    }
+
+    #[test]
+    fn test_list_dir_missing() {
+        let members = vec![member("sub/file.txt", S_IFREG | 0o644)];
+        assert!(list_dir(&members, "other", SystemTime::UNIX_EPOCH).is_err());
+        // a name prefix is not a parent directory
+        assert!(list_dir(&members, "su", SystemTime::UNIX_EPOCH).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use std::ffi::CString;
+use std::fs::OpenOptions;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+// linux/fs.h: _IOR('f', 1, long)
+#[cfg(target_pointer_width = "64")]
+const FS_IOC_GETFLAGS: u64 = 0x80086601;
+#[cfg(target_pointer_width = "32")]
+const FS_IOC_GETFLAGS: u64 = 0x80046601;
+
+const FS_IMMUTABLE_FL: u32 = 0x00000010;
+const FS_APPEND_FL: u32 = 0x00000020;
+const FS_NODUMP_FL: u32 = 0x00000040;
+
+// linux/capability.h
+const XATTR_NAME_CAPS: &str = "security.capability";
+const VFS_CAP_REVISION_MASK: u32 = 0xFF000000;
+const VFS_CAP_REVISION_1: u32 = 0x01000000;
+const VFS_CAP_REVISION_2: u32 = 0x02000000;
+const VFS_CAP_REVISION_3: u32 = 0x03000000;
+const VFS_CAP_FLAGS_EFFECTIVE: u32 = 0x000001;
+
+const CAP_NAMES: [&str; 41] = [
+    "cap_chown",
+    "cap_dac_override",
+    "cap_dac_read_search",
+    "cap_fowner",
+    "cap_fsetid",
+    "cap_kill",
+    "cap_setgid",
+    "cap_setuid",
+    "cap_setpcap",
+    "cap_linux_immutable",
+    "cap_net_bind_service",
+    "cap_net_broadcast",
+    "cap_net_admin",
+    "cap_net_raw",
+    "cap_ipc_lock",
+    "cap_ipc_owner",
+    "cap_sys_module",
+    "cap_sys_rawio",
+    "cap_sys_chroot",
+    "cap_sys_ptrace",
+    "cap_sys_pacct",
+    "cap_sys_admin",
+    "cap_sys_boot",
+    "cap_sys_nice",
+    "cap_sys_resource",
+    "cap_sys_time",
+    "cap_sys_tty_config",
+    "cap_mknod",
+    "cap_lease",
+    "cap_audit_write",
+    "cap_audit_control",
+    "cap_setfcap",
+    "cap_mac_override",
+    "cap_mac_admin",
+    "cap_syslog",
+    "cap_wake_alarm",
+    "cap_block_suspend",
+    "cap_audit_read",
+    "cap_perfmon",
+    "cap_bpf",
+    "cap_checkpoint_restore",
+];
+
+/// Read the security.capability xattr of a file (without following symlinks)
+/// and decode it in getcap style, ie: cap_net_admin,cap_net_raw=ep
+///
+/// Returns None when the file has no capabilities set.
+pub fn read_capabilities(path: &Path) -> Option<String> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let c_name = CString::new(XATTR_NAME_CAPS).ok()?;
+    let mut buffer = [0u8; 64];
+
+    let len = unsafe {
+        libc::lgetxattr(
+            c_path.as_ptr(),
+            c_name.as_ptr(),
+            buffer.as_mut_ptr() as *mut libc::c_void,
+            buffer.len(),
+        )
+    };
+    if len < 0 {
+        return None;
+    }
+
+    decode_capabilities(&buffer[..len as usize])
+}
+
+fn read_le_u32(data: &[u8], idx: usize) -> Option<u32> {
+    let bytes = data.get(idx * 4..idx * 4 + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decode a vfs_cap_data struct:
+///   magic_etc, then (permitted, inheritable) pairs for each 32 capabilities
+///   and for revision 3 a trailing rootid
+fn decode_capabilities(data: &[u8]) -> Option<String> {
+    let magic = read_le_u32(data, 0)?;
+    let words = match magic & VFS_CAP_REVISION_MASK {
+        VFS_CAP_REVISION_1 => 1,
+        VFS_CAP_REVISION_2 | VFS_CAP_REVISION_3 => 2,
+        _ => return Some("?".to_string()),
+    };
+    let effective = magic & VFS_CAP_FLAGS_EFFECTIVE == VFS_CAP_FLAGS_EFFECTIVE;
+
+    let mut permitted: u64 = 0;
+    let mut inheritable: u64 = 0;
+    for word in 0..words {
+        permitted |= (read_le_u32(data, 1 + word * 2)? as u64) << (32 * word);
+        inheritable |= (read_le_u32(data, 2 + word * 2)? as u64) << (32 * word);
+    }
+
+    // group capabilities sharing the same set of flags like getcap does
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for bit in 0..64 {
+        let mut flags = String::new();
+        if effective && permitted & (1 << bit) != 0 {
+            flags.push('e');
+        }
+        if inheritable & (1 << bit) != 0 {
+            flags.push('i');
+        }
+        if permitted & (1 << bit) != 0 {
+            flags.push('p');
+        }
+        if flags.is_empty() {
+            continue;
+        }
+        let name = match CAP_NAMES.get(bit) {
+            Some(name) => name.to_string(),
+            None => format!("cap_{}", bit),
+        };
+        match groups
+            .iter_mut()
+            .find(|(group_flags, _)| *group_flags == flags)
+        {
+            Some((_, names)) => names.push(name),
+            None => groups.push((flags, vec![name])),
+        }
+    }
+
+    if groups.is_empty() {
+        return None;
+    }
+
+    let decoded: Vec<String> = groups
+        .iter()
+        .map(|(flags, names)| format!("{}={}", names.join(","), flags))
+        .collect();
+    Some(decoded.join(" "))
+}
+
+/// Read the inode flags (as shown by lsattr) of a regular file or directory.
+/// Returns None when the filesystem does not support them.
+pub fn read_inode_flags(path: &Path) -> Option<u32> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+
+    let mut flags: libc::c_int = 0;
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_GETFLAGS as _, &mut flags) };
+    if ret < 0 {
+        return None;
+    }
+    Some(flags as u32)
+}
+
+/// Render the inode flags we care about in lsattr letters:
+/// i: immutable, a: append only, d: no dump
+pub fn format_inode_flags(flags: u32) -> String {
+    let mut attrs = String::new();
+
+    if flags & FS_IMMUTABLE_FL == FS_IMMUTABLE_FL {
+        attrs.push('i');
+    } else {
+        attrs.push('-');
+    }
+    if flags & FS_APPEND_FL == FS_APPEND_FL {
+        attrs.push('a');
+    } else {
+        attrs.push('-');
+    }
+    if flags & FS_NODUMP_FL == FS_NODUMP_FL {
+        attrs.push('d');
+    } else {
+        attrs.push('-');
+    }
+
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// vfs_cap_data blob from magic_etc and the (permitted, inheritable) words
+    fn cap_blob(magic: u32, words: &[(u32, u32)], rootid: Option<u32>) -> Vec<u8> {
+        let mut data = magic.to_le_bytes().to_vec();
+        for (permitted, inheritable) in words {
+            data.extend_from_slice(&permitted.to_le_bytes());
+            data.extend_from_slice(&inheritable.to_le_bytes());
+        }
+        if let Some(rootid) = rootid {
+            data.extend_from_slice(&rootid.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_caps_v2_effective() {
+        // cap_net_admin (12) and cap_net_raw (13)
+        let magic = VFS_CAP_REVISION_2 | VFS_CAP_FLAGS_EFFECTIVE;
+        let data = cap_blob(magic, &[(0x3000, 0), (0, 0)], None);
+        assert_eq!(
+            decode_capabilities(&data),
+            Some("cap_net_admin,cap_net_raw=ep".to_string())
+        );
+    }
+
+    #[test]
+    fn test_caps_v2_high_word() {
+        // cap_net_bind_service (10) and cap_bpf (39), not effective
+        let data = cap_blob(VFS_CAP_REVISION_2, &[(1 << 10, 0), (1 << 7, 0)], None);
+        assert_eq!(
+            decode_capabilities(&data),
+            Some("cap_net_bind_service,cap_bpf=p".to_string())
+        );
+    }
+
+    #[test]
+    fn test_caps_v2_groups() {
+        // cap_chown (0) inheritable and permitted, cap_kill (5) only permitted
+        let magic = VFS_CAP_REVISION_2 | VFS_CAP_FLAGS_EFFECTIVE;
+        let data = cap_blob(magic, &[(0x21, 0x01), (0, 0)], None);
+        assert_eq!(
+            decode_capabilities(&data),
+            Some("cap_chown=eip cap_kill=ep".to_string())
+        );
+    }
+
+    #[test]
+    fn test_caps_v3_rootid() {
+        // same sets as v2, the trailing rootid does not change them
+        let magic = VFS_CAP_REVISION_3 | VFS_CAP_FLAGS_EFFECTIVE;
+        let data = cap_blob(magic, &[(1 << 21, 0), (0, 0)], Some(100000));
+        assert_eq!(
+            decode_capabilities(&data),
+            Some("cap_sys_admin=ep".to_string())
+        );
+    }
+
+    #[test]
+    fn test_caps_unknown_bit() {
+        let data = cap_blob(VFS_CAP_REVISION_3, &[(0, 0), (1 << 31, 0)], Some(0));
+        assert_eq!(decode_capabilities(&data), Some("cap_63=p".to_string()));
+    }
+
+    #[test]
+    fn test_caps_empty_sets() {
+        let data = cap_blob(VFS_CAP_REVISION_2, &[(0, 0), (0, 0)], None);
+        assert_eq!(decode_capabilities(&data), None);
+    }
+
+    #[test]
+    fn test_caps_truncated() {
+        let data = cap_blob(VFS_CAP_REVISION_2, &[(0x3000, 0)], None);
+        assert_eq!(decode_capabilities(&data), None);
+        assert_eq!(decode_capabilities(&[0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn test_caps_unknown_revision() {
+        let data = cap_blob(0x04000000, &[(0x3000, 0), (0, 0)], None);
+        assert_eq!(decode_capabilities(&data), Some("?".to_string()));
+    }
+
+    #[test]
+    fn test_format_inode_flags() {
+        assert_eq!(format_inode_flags(0), "---");
+        assert_eq!(format_inode_flags(FS_IMMUTABLE_FL | FS_NODUMP_FL), "i-d");
+        assert_eq!(format_inode_flags(FS_APPEND_FL), "-a-");
+    }
+}
@@ -0,0 +1,193 @@
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Column {
+    /// File type and permissions
+    Perm,
+    /// Inode flags (same as --attrs)
+    Attrs,
+    Links,
+    User,
+    Group,
+    Size,
+    Mtime,
+    /// Filesystem for mount crossings (same as --fs)
+    Fs,
+    /// File capabilities (same as --caps)
+    Caps,
+    Name,
+    /// Symlink target
+    Target,
+    /// Directory being listed
+    Dir,
+}
+
+impl Column {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Perm => "perm",
+            Column::Attrs => "attrs",
+            Column::Links => "links",
+            Column::User => "user",
+            Column::Group => "group",
+            Column::Size => "size",
+            Column::Mtime => "mtime",
+            Column::Fs => "fs",
+            Column::Caps => "caps",
+            Column::Name => "name",
+            Column::Target => "target",
+            Column::Dir => "dir",
+        }
+    }
+
+    /// Numeric like columns are right aligned, same as ls does
+    pub fn is_right_aligned(&self) -> bool {
+        matches!(
+            self,
+            Column::Links | Column::User | Column::Group | Column::Size
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for the terminal
+    Table,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn tsv_escape(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+fn markdown_escape(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+/// Format a single row of cells for one of the export formats
+pub fn format_row(cells: &[String], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Csv => {
+            let cells: Vec<String> = cells.iter().map(|cell| csv_escape(cell)).collect();
+            cells.join(",")
+        }
+        OutputFormat::Tsv => {
+            let cells: Vec<String> = cells.iter().map(|cell| tsv_escape(cell)).collect();
+            cells.join("\t")
+        }
+        OutputFormat::Markdown => {
+            let cells: Vec<String> = cells.iter().map(|cell| markdown_escape(cell)).collect();
+            format!("| {} |", cells.join(" | "))
+        }
+        OutputFormat::Table => cells.join(" "),
+    }
+}
+
+/// Header line(s) for the export formats
+pub fn format_header(columns: &[Column], format: OutputFormat) -> String {
+    let names: Vec<String> = columns.iter().map(|col| col.name().to_string()).collect();
+    let header = format_row(&names, format);
+    if format != OutputFormat::Markdown {
+        return header;
+    }
+
+    let separators: Vec<&str> = columns
+        .iter()
+        .map(|col| {
+            if col.is_right_aligned() {
+                "---:"
+            } else {
+                "---"
+            }
+        })
+        .collect();
+    format!("{}\n| {} |", header, separators.join(" | "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_csv_plain() {
+        let row = format_row(&cells(&["-rw-r--r--", "file.txt", "12"]), OutputFormat::Csv);
+        assert_eq!(row, "-rw-r--r--,file.txt,12");
+    }
+
+    #[test]
+    fn test_csv_comma() {
+        let row = format_row(&cells(&["a,b", "c"]), OutputFormat::Csv);
+        assert_eq!(row, "\"a,b\",c");
+    }
+
+    #[test]
+    fn test_csv_quotes() {
+        let row = format_row(&cells(&["say \"hi\"", "plain"]), OutputFormat::Csv);
+        assert_eq!(row, "\"say \"\"hi\"\"\",plain");
+    }
+
+    #[test]
+    fn test_csv_newline() {
+        let row = format_row(&cells(&["two\nlines"]), OutputFormat::Csv);
+        assert_eq!(row, "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_csv_tab_and_pipe_untouched() {
+        let row = format_row(&cells(&["a\tb", "c|d"]), OutputFormat::Csv);
+        assert_eq!(row, "a\tb,c|d");
+    }
+
+    #[test]
+    fn test_tsv_tab() {
+        let row = format_row(&cells(&["a\tb", "c,d", "e\r\nf"]), OutputFormat::Tsv);
+        assert_eq!(row, "a b\tc,d\te  f");
+    }
+
+    #[test]
+    fn test_tsv_quotes_untouched() {
+        let row = format_row(&cells(&["\"quoted\"", "x"]), OutputFormat::Tsv);
+        assert_eq!(row, "\"quoted\"\tx");
+    }
+
+    #[test]
+    fn test_markdown_pipe() {
+        let row = format_row(&cells(&["a|b", "c"]), OutputFormat::Markdown);
+        assert_eq!(row, "| a\\|b | c |");
+    }
+
+    #[test]
+    fn test_markdown_newline_comma_quotes() {
+        let row = format_row(&cells(&["two\nlines", "a,\"b\""]), OutputFormat::Markdown);
+        assert_eq!(row, "| two lines | a,\"b\" |");
+    }
+
+    #[test]
+    fn test_header_csv() {
+        let header = format_header(
+            &[Column::Perm, Column::Size, Column::Name],
+            OutputFormat::Csv,
+        );
+        assert_eq!(header, "perm,size,name");
+    }
+
+    #[test]
+    fn test_header_markdown() {
+        let header = format_header(&[Column::Name, Column::Size], OutputFormat::Markdown);
+        assert_eq!(header, "| name | size |\n| --- | ---: |");
+    }
+}
@@ -0,0 +1,329 @@
+use clap::ValueEnum;
+use devicons::{icon_for_file, Theme};
+use libc::{S_IFLNK, S_IFMT};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{stdout, IsTerminal};
+use std::path::{Path, PathBuf};
+
+const DIR_COLOR: &str = "#3483eb";
+const DIR_ICON_NERD: &str = "\u{e6ad}";
+
+/// Key used in the icon map file to override the directory icon
+const DIR_MAP_KEY: &str = "<dir>";
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum IconMode {
+    /// Icons when writing to a terminal, ascii fallback on the linux console
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum IconSet {
+    /// Nerd Font glyphs
+    Nerd,
+    Emoji,
+    Ascii,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ColorTheme {
+    Dark,
+    Light,
+}
+
+struct CustomIcon {
+    icon: String,
+    color: Option<String>,
+}
+
+pub struct IconConfig {
+    /// None when icons are disabled
+    set: Option<IconSet>,
+    theme: Option<Theme>,
+    custom: HashMap<String, CustomIcon>,
+}
+
+impl IconConfig {
+    pub fn new() -> IconConfig {
+        IconConfig {
+            set: Some(IconSet::Nerd),
+            theme: Some(Theme::Dark),
+            custom: HashMap::new(),
+        }
+    }
+
+    pub fn configure(&mut self, mode: IconMode, set: IconSet, theme: ColorTheme) {
+        self.set = match mode {
+            IconMode::Always => Some(set),
+            IconMode::Never => None,
+            IconMode::Auto => {
+                if !stdout().is_terminal() {
+                    None
+                } else if is_plain_console() && set == IconSet::Nerd {
+                    Some(IconSet::Ascii)
+                } else {
+                    Some(set)
+                }
+            }
+        };
+        self.theme = match theme {
+            ColorTheme::Dark => Some(Theme::Dark),
+            ColorTheme::Light => Some(Theme::Light),
+        };
+    }
+
+    /// Load the user icon map file. Each line has the form:
+    ///
+    ///     key:icon[:#rrggbb]
+    ///
+    /// where key is a full filename, an extension or <dir> for directories.
+    /// Lines starting with # are comments.
+    pub fn load_map(&mut self, path: &Path) {
+        match fs::read_to_string(path) {
+            Ok(data) => self.parse_map(&data),
+            Err(e) => eprintln!("Failed to read {:?} file. Reason: {}", path, e),
+        }
+    }
+
+    /// Add the entries of an icon map, invalid lines are reported and skipped
+    fn parse_map(&mut self, data: &str) {
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cols: Vec<&str> = line.split(':').collect();
+            if cols.len() < 2 || cols[0].is_empty() || cols[1].is_empty() {
+                eprintln!("Invalid icon map entry [{}]", line);
+                continue;
+            }
+            let color = match cols.get(2) {
+                Some(color) => {
+                    if let Err(e) = crate::extract_rgb(color) {
+                        eprintln!("Invalid color in icon map entry [{}]: {}", line, e);
+                        continue;
+                    }
+                    Some(color.to_string())
+                }
+                None => None,
+            };
+            self.custom.insert(
+                cols[0].to_string(),
+                CustomIcon {
+                    icon: cols[1].to_string(),
+                    color,
+                },
+            );
+        }
+    }
+
+    /// Return the icon (empty when disabled) and color for an entry.
+    /// mode should not follow symlinks, while is_dir should so symlinks to
+    /// directories still get the directory icon.
+    pub fn lookup(&self, path: &Path, is_dir: bool, mode: u32) -> (String, String) {
+        let (mut icon, mut color) = if is_dir {
+            (self.builtin_dir_icon(), DIR_COLOR.to_string())
+        } else {
+            // TODO: review the color or any other special handling for symlink
+            let file_icon = icon_for_file(path, &self.theme);
+            let icon = match self.set {
+                Some(IconSet::Nerd) => file_icon.icon.to_string(),
+                Some(IconSet::Emoji) => emoji_icon(path, mode).to_string(),
+                Some(IconSet::Ascii) => ascii_icon(mode).to_string(),
+                None => String::new(),
+            };
+            (icon, file_icon.color.to_string())
+        };
+
+        if let Some(custom) = self.find_custom(path, is_dir) {
+            if self.set.is_some() {
+                icon = custom.icon.clone();
+            }
+            if let Some(custom_color) = &custom.color {
+                color = custom_color.clone();
+            }
+        }
+
+        (icon, color)
+    }
+
+    fn builtin_dir_icon(&self) -> String {
+        match self.set {
+            Some(IconSet::Nerd) => DIR_ICON_NERD.to_string(),
+            Some(IconSet::Emoji) => "📁".to_string(),
+            Some(IconSet::Ascii) => "/".to_string(),
+            None => String::new(),
+        }
+    }
+
+    fn find_custom(&self, path: &Path, is_dir: bool) -> Option<&CustomIcon> {
+        if is_dir {
+            return self.custom.get(DIR_MAP_KEY);
+        }
+        let filename = path.file_name()?.to_str()?;
+        if let Some(custom) = self.custom.get(filename) {
+            return Some(custom);
+        }
+        let extension = path.extension()?.to_str()?;
+        self.custom.get(extension)
+    }
+}
+
+/// Default location of the icon map: $XDG_CONFIG_HOME/myls/icons or
+/// ~/.config/myls/icons
+pub fn default_map_path() -> Option<PathBuf> {
+    let config_dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+    Some(config_dir.join("myls").join("icons"))
+}
+
+/// The linux virtual console (and dumb terminals) can not render Nerd Font
+/// glyphs, they just show tofu.
+fn is_plain_console() -> bool {
+    match env::var("TERM") {
+        Ok(term) => term == "linux" || term == "dumb",
+        Err(_) => true,
+    }
+}
+
+fn is_symlink(mode: u32) -> bool {
+    mode & S_IFMT == S_IFLNK
+}
+
+fn is_executable(mode: u32) -> bool {
+    mode & 0o111 != 0
+}
+
+fn ascii_icon(mode: u32) -> &'static str {
+    // same indicators as ls -F, plain files have none
+    if is_symlink(mode) {
+        "@"
+    } else if is_executable(mode) {
+        "*"
+    } else {
+        ""
+    }
+}
+
+fn emoji_icon(path: &Path, mode: u32) -> &'static str {
+    if is_symlink(mode) {
+        return "🔗";
+    }
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match extension.as_str() {
+        "zip" | "tar" | "gz" | "tgz" | "xz" | "bz2" | "zst" | "7z" | "rar" => "📦",
+        "png" | "jpg" | "jpeg" | "gif" | "bmp" | "svg" | "webp" | "ico" => "🖼",
+        "mp3" | "wav" | "flac" | "ogg" | "m4a" => "🎵",
+        "mp4" | "mkv" | "avi" | "mov" | "webm" => "🎬",
+        "pdf" => "📕",
+        "md" | "txt" | "rst" => "📝",
+        "rs" | "c" | "h" | "cpp" | "py" | "go" | "js" | "ts" | "java" | "sh" => "📜",
+        "toml" | "json" | "yaml" | "yml" | "ini" | "conf" => "🔧",
+        _ => {
+            if is_executable(mode) {
+                "⚙"
+            } else {
+                "📄"
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libc::S_IFREG;
+
+    const REGULAR: u32 = S_IFREG | 0o644;
+
+    fn config(mode: IconMode, set: IconSet, map: &str) -> IconConfig {
+        let mut config = IconConfig::new();
+        config.configure(mode, set, ColorTheme::Dark);
+        config.parse_map(map);
+        config
+    }
+
+    #[test]
+    fn test_parse_map() {
+        let config = config(
+            IconMode::Always,
+            IconSet::Ascii,
+            "# comment\n\n  rs:R:#ff0000  \nbad:B:#zz0000\nnoicon:\n:X\nmd:M\n",
+        );
+        assert_eq!(config.custom.len(), 2);
+        assert_eq!(config.custom["rs"].icon, "R");
+        assert_eq!(config.custom["rs"].color.as_deref(), Some("#ff0000"));
+        assert_eq!(config.custom["md"].icon, "M");
+        assert_eq!(config.custom["md"].color, None);
+        assert!(!config.custom.contains_key("bad"));
+        assert!(!config.custom.contains_key("noicon"));
+    }
+
+    #[test]
+    fn test_lookup_filename_before_extension() {
+        let config = config(
+            IconMode::Always,
+            IconSet::Ascii,
+            "rs:R:#ff0000\nmain.rs:M:#00ff00\n",
+        );
+        assert_eq!(
+            config.lookup(Path::new("src/main.rs"), false, REGULAR),
+            ("M".to_string(), "#00ff00".to_string())
+        );
+        assert_eq!(
+            config.lookup(Path::new("src/lib.rs"), false, REGULAR),
+            ("R".to_string(), "#ff0000".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lookup_dir_key() {
+        let config = config(IconMode::Always, IconSet::Emoji, "<dir>:D\n");
+        let (icon, color) = config.lookup(Path::new("src"), true, 0);
+        assert_eq!(icon, "D");
+        assert_eq!(color, DIR_COLOR);
+        assert_eq!(config.lookup(Path::new("dir"), false, REGULAR).0, "📄");
+    }
+
+    #[test]
+    fn test_lookup_never() {
+        let config = config(IconMode::Never, IconSet::Nerd, "rs:R:#ff0000\n");
+        // the custom color is still applied without icons
+        assert_eq!(
+            config.lookup(Path::new("main.rs"), false, REGULAR),
+            (String::new(), "#ff0000".to_string())
+        );
+        assert_eq!(config.lookup(Path::new("src"), true, 0).0, "");
+    }
+
+    #[test]
+    fn test_lookup_ascii() {
+        let config = config(IconMode::Always, IconSet::Ascii, "");
+        let lookup = |mode| config.lookup(Path::new("file"), false, mode).0;
+        assert_eq!(lookup(REGULAR), "");
+        assert_eq!(lookup(S_IFREG | 0o755), "*");
+        assert_eq!(lookup(S_IFLNK | 0o777), "@");
+        assert_eq!(config.lookup(Path::new("src"), true, 0).0, "/");
+    }
+
+    #[test]
+    fn test_lookup_emoji() {
+        let config = config(IconMode::Always, IconSet::Emoji, "");
+        let lookup = |name, mode| config.lookup(Path::new(name), false, mode).0;
+        assert_eq!(lookup("a.tar.gz", REGULAR), "📦");
+        assert_eq!(lookup("README.MD", REGULAR), "📝");
+        assert_eq!(lookup("run", S_IFREG | 0o755), "⚙");
+        assert_eq!(lookup("link.rs", S_IFLNK | 0o777), "🔗");
+        assert_eq!(config.lookup(Path::new("src"), true, 0).0, "📁");
+    }
+}
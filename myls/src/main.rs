@@ -1,14 +1,13 @@
 use chrono::{DateTime, Datelike, Local};
 use clap::Parser;
-use devicons::{icon_for_file, Theme};
 use libc::{
-    S_IRGRP, S_IROTH, S_IRUSR, S_ISGID, S_ISUID, S_ISVTX, S_IWGRP, S_IWOTH, S_IWUSR, S_IXGRP,
-    S_IXOTH, S_IXUSR,
+    S_IFBLK, S_IFCHR, S_IFDIR, S_IFLNK, S_IFMT, S_IRGRP, S_IROTH, S_IRUSR, S_ISGID, S_ISUID,
+    S_ISVTX, S_IWGRP, S_IWOTH, S_IWUSR, S_IXGRP, S_IXOTH, S_IXUSR,
 };
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::{self, Metadata};
-use std::os::unix::fs::FileTypeExt;
+use std::fs;
 use std::os::unix::fs::MetadataExt;
 use std::path::Path;
 use std::path::PathBuf;
@@ -16,6 +15,16 @@ use std::process::exit;
 use std::str::FromStr;
 use std::time::SystemTime;
 
+mod archive;
+mod attrs;
+mod columns;
+mod icons;
+mod mounts;
+use archive::ArchiveMember;
+use columns::{Column, OutputFormat};
+use icons::{ColorTheme, IconConfig, IconMode, IconSet};
+use mounts::MountTable;
+
 const KB: f64 = 1024.0;
 const MB: f64 = 1024.0 * KB;
 const GB: f64 = 1024.0 * MB;
@@ -25,6 +34,9 @@ const ERR_EMPTY_STRING: &str = "Empty Hex String";
 const ERR_INVALID_FORMAT: &str = "Invalid format (must be #XXXXXX)";
 const ERR_PARSE_ERROR: &str = "Failed Parsing Hex Value";
 
+const MOUNT_POINT_COLOR: &str = "#e5c07b";
+const SETCAP_COLOR: &str = "#e06c75";
+
 /// Simple LS implementation
 #[derive(Parser)]
 #[command(version, disable_help_flag = true)]
@@ -52,6 +64,54 @@ struct Args {
     #[arg(short, long)]
     human: bool,
 
+    /// List subdirectories recursively
+    #[arg(short = 'R', long)]
+    recursive: bool,
+
+    /// Show filesystem type and mount source for entries on another filesystem
+    #[arg(long = "fs")]
+    show_fs: bool,
+
+    /// Do not descend into directories on other filesystems
+    #[arg(long)]
+    one_file_system: bool,
+
+    /// Show the total size of the contents of directories, like du
+    #[arg(long)]
+    total_size: bool,
+
+    /// Show file capabilities (security.capability)
+    #[arg(long)]
+    caps: bool,
+
+    /// Show immutable, append-only and nodump inode flags
+    #[arg(long)]
+    attrs: bool,
+
+    /// When to show icons
+    #[arg(long, value_enum, default_value_t = IconMode::Auto)]
+    icons: IconMode,
+
+    /// Icon set to use, emoji and ascii do not require a Nerd Font
+    #[arg(long, value_enum, default_value_t = IconSet::Nerd)]
+    icon_set: IconSet,
+
+    /// Color theme for file icons
+    #[arg(long, value_enum, default_value_t = ColorTheme::Dark)]
+    theme: ColorTheme,
+
+    /// File with extra extension to icon/color mappings
+    #[arg(long)]
+    icon_map: Option<String>,
+
+    /// Comma separated list of columns for the long listing
+    #[arg(long, value_enum, value_delimiter = ',')]
+    columns: Vec<Column>,
+
+    /// Output format for the long listing
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
     /// Show help
     #[arg(long)]
     help: bool,
@@ -60,23 +120,31 @@ struct Args {
 #[derive(Debug)]
 struct FileItem {
     filename: String,
-    abs_metadata: Metadata,
-    sym_metadata: Metadata,
+    ftype: char,
+    nlink: u64,
+    dev: u64,
     symlink_path: String,
     filesize: u64,
     human_readable_size: String,
     user: String,
     group: String,
     color: String,
-    icon: char,
+    icon: String,
     modified: String,
     last_modified: SystemTime,
     mode: u32,
+    crosses_fs: bool,
+    is_mount_point: bool,
+    fs_info: String,
+    capabilities: Option<String>,
+    inode_flags: Option<u32>,
 }
 
 struct Context {
     uid_map: HashMap<u32, String>,
     gid_map: HashMap<u32, String>,
+    mounts: MountTable,
+    icons: IconConfig,
     flags: ListingFlags,
 }
 
@@ -85,6 +153,8 @@ impl Context {
         Context {
             uid_map: HashMap::new(),
             gid_map: HashMap::new(),
+            mounts: MountTable::new(),
+            icons: IconConfig::new(),
             flags: ListingFlags::new(),
         }
     }
@@ -96,6 +166,14 @@ struct ListingFlags {
     reverse_sort: bool,
     sort_by_time: bool,
     human_readable: bool,
+    recursive: bool,
+    show_fs: bool,
+    one_file_system: bool,
+    total_size: bool,
+    show_caps: bool,
+    show_attrs: bool,
+    columns: Vec<Column>,
+    format: OutputFormat,
 }
 
 impl ListingFlags {
@@ -106,6 +184,14 @@ impl ListingFlags {
             reverse_sort: false,
             sort_by_time: false,
             human_readable: false,
+            recursive: false,
+            show_fs: false,
+            one_file_system: false,
+            total_size: false,
+            show_caps: false,
+            show_attrs: false,
+            columns: Vec::new(),
+            format: OutputFormat::Table,
         }
     }
 }
@@ -219,7 +305,29 @@ fn get_current_path() -> String {
     }
 }
 
-fn parse_file_entry(path: &String, ctx: &Context) -> Result<FileItem, String> {
+fn format_modified(last_modified: SystemTime) -> String {
+    let ltime: DateTime<Local> = DateTime::from(last_modified);
+    let file_year = ltime.year();
+    let current_time = Local::now();
+    let current_year = current_time.year();
+    if current_year != file_year {
+        format!("{}", ltime.format("%b %e  %Y"))
+    } else {
+        format!("{}", ltime.format("%b %e %H:%M"))
+    }
+}
+
+fn get_file_type(mode: u32) -> char {
+    match mode & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        S_IFCHR => 'c',
+        S_IFBLK => 'b',
+        _ => '-',
+    }
+}
+
+fn parse_file_entry(path: &String, parent_dev: u64, ctx: &Context) -> Result<FileItem, String> {
     let my_path = Path::new(path);
 
     // Use symlink_metadata to not traverse any symbolic my_links
@@ -231,11 +339,10 @@ fn parse_file_entry(path: &String, ctx: &Context) -> Result<FileItem, String> {
         }
     };
 
-    let abs_metadata = match my_path.metadata() {
-        Ok(data) => data,
-        Err(_) => {
-            return Err("Failed to get abs metadata".to_string());
-        }
+    // broken symlinks have no target to follow, they are listed all the same
+    let is_dir = match my_path.metadata() {
+        Ok(data) => data.is_dir(),
+        Err(_) => sym_metadata.is_dir(),
     };
 
     let os_filename = match my_path.file_name() {
@@ -248,21 +355,8 @@ fn parse_file_entry(path: &String, ctx: &Context) -> Result<FileItem, String> {
         Err(_) => return Err("Failed to parse filename".to_string()),
     };
 
-    let my_icon: char;
-    let my_color: String;
-    if my_path.is_dir() {
-        my_icon = '';
-        my_color = "#3483eb".to_string()
-    } else if my_path.is_file() {
-        let icon = icon_for_file(Path::new(&my_path), Some(Theme::Dark));
-        my_icon = icon.icon;
-        my_color = icon.color.to_string();
-    } else {
-        // TODO: review the color or any other special handling for symlink
-        let icon = icon_for_file(Path::new(&my_path), Some(Theme::Dark));
-        my_icon = icon.icon;
-        my_color = icon.color.to_string();
-    }
+    let mode = sym_metadata.mode();
+    let (my_icon, my_color) = ctx.icons.lookup(my_path, is_dir, mode);
 
     let user = match ctx.uid_map.get(&sym_metadata.uid()) {
         Some(user) => user.to_owned(),
@@ -282,18 +376,6 @@ fn parse_file_entry(path: &String, ctx: &Context) -> Result<FileItem, String> {
         }
     };
 
-    let ltime: DateTime<Local> = DateTime::from(last_modified);
-    let file_year = ltime.year();
-    let current_time = Local::now();
-    let current_year = current_time.year();
-    let modified_time = if current_year != file_year {
-        format!("{}", ltime.format("%b %e  %Y"))
-    } else {
-        format!("{}", ltime.format("%b %e %H:%M"))
-    };
-
-    let mode = sym_metadata.mode();
-
     let symlink_path: String = if sym_metadata.is_symlink() {
         let path = match fs::read_link(my_path) {
             Ok(path) => path,
@@ -308,30 +390,69 @@ fn parse_file_entry(path: &String, ctx: &Context) -> Result<FileItem, String> {
         String::new()
     };
 
+    // check if we jumped into another filesystem
+    let crosses_fs = sym_metadata.dev() != parent_dev;
+    let mut is_mount_point = false;
+    let mut fs_info = String::new();
+    if ctx.flags.show_fs {
+        let canonical = fs::canonicalize(my_path).unwrap_or_else(|_| my_path.to_path_buf());
+        is_mount_point = sym_metadata.is_dir() && ctx.mounts.is_mount_point(&canonical);
+        if crosses_fs || is_mount_point {
+            let mount = match ctx.mounts.find_by_dev(sym_metadata.dev()) {
+                Some(mount) => Some(mount),
+                None => ctx.mounts.find_by_path(&canonical),
+            };
+            fs_info = match mount {
+                Some(mount) => mount.describe(),
+                None => "?".to_string(),
+            };
+        }
+    }
+
+    let capabilities = if ctx.flags.show_caps && sym_metadata.is_file() {
+        attrs::read_capabilities(my_path)
+    } else {
+        None
+    };
+
+    // only open regular files and directories, opening devices may have side effects
+    let inode_flags = if ctx.flags.show_attrs && (sym_metadata.is_file() || sym_metadata.is_dir()) {
+        attrs::read_inode_flags(my_path)
+    } else {
+        None
+    };
+
     let item = FileItem {
         filename: my_filename,
-        sym_metadata: sym_metadata.clone(),
-        abs_metadata: abs_metadata.clone(),
+        ftype: get_file_type(mode),
+        nlink: sym_metadata.nlink(),
+        dev: sym_metadata.dev(),
         symlink_path,
         filesize: sym_metadata.len(),
         human_readable_size: get_human_readable(sym_metadata.len()),
         user,
         group,
-        modified: modified_time,
+        modified: format_modified(last_modified),
         color: my_color,
         icon: my_icon,
         mode,
         last_modified,
+        crosses_fs,
+        is_mount_point,
+        fs_info,
+        capabilities,
+        inode_flags,
     };
 
     Ok(item)
 }
 
-fn parse_directory(path: &Path, items: &mut Vec<String>) {
+fn parse_directory(path: &Path, items: &mut Vec<String>, had_error: &mut bool) {
     let data = match fs::read_dir(path) {
         Ok(x) => x,
         Err(e) => {
             eprintln!("ERROR: Failed to read directory ({:?})->({:?})", path, e);
+            *had_error = true;
             // return empty vec
             return;
         }
@@ -352,23 +473,156 @@ fn parse_directory(path: &Path, items: &mut Vec<String>) {
     });
 }
 
-fn parse_path(target_path: &String, ctx: &Context) -> Vec<FileItem> {
+fn get_dev(path: &Path) -> u64 {
+    match path.metadata() {
+        Ok(meta) => meta.dev(),
+        Err(e) => {
+            eprintln!("ERROR: Failed to read metadata for {:?}: {}", path, e);
+            0
+        }
+    }
+}
+
+/// Device of a command line target, for archive paths use the archive file
+fn get_target_dev(target_path: &str) -> u64 {
+    match archive::split_archive_path(target_path) {
+        Some((archive_path, _)) => get_dev(Path::new(&archive_path)),
+        None => get_dev(Path::new(target_path)),
+    }
+}
+
+fn archive_member_item(member: &ArchiveMember, dev: u64, ctx: &Context) -> FileItem {
+    let (my_icon, my_color) =
+        ctx.icons
+            .lookup(Path::new(&member.path), member.is_dir(), member.mode);
+
+    // prefer the names stored in the archive, fallback to the local ones
+    let user = match (&member.user, member.uid) {
+        (Some(user), _) if !user.is_empty() => user.to_owned(),
+        (_, Some(uid)) => match ctx.uid_map.get(&uid) {
+            Some(user) => user.to_owned(),
+            None => uid.to_string(),
+        },
+        _ => "----".to_owned(),
+    };
+
+    let group = match (&member.group, member.gid) {
+        (Some(group), _) if !group.is_empty() => group.to_owned(),
+        (_, Some(gid)) => match ctx.gid_map.get(&gid) {
+            Some(group) => group.to_owned(),
+            None => gid.to_string(),
+        },
+        _ => "----".to_owned(),
+    };
+
+    FileItem {
+        filename: member.filename().to_string(),
+        ftype: get_file_type(member.mode),
+        nlink: 1,
+        dev,
+        symlink_path: member.symlink_path.clone(),
+        filesize: member.size,
+        human_readable_size: get_human_readable(member.size),
+        user,
+        group,
+        color: my_color,
+        icon: my_icon,
+        modified: format_modified(member.last_modified),
+        last_modified: member.last_modified,
+        mode: member.mode,
+        crosses_fs: false,
+        is_mount_point: false,
+        fs_info: String::new(),
+        capabilities: None,
+        inode_flags: None,
+    }
+}
+
+/// Members of the archives read so far, by archive path, so -R reads each
+/// archive once instead of once per directory inside it
+type ArchiveCache = HashMap<String, Vec<ArchiveMember>>;
+
+/// List a directory inside an archive as if it was a regular directory,
+/// failures are reported and set had_error
+fn parse_archive(
+    archive_path: &str,
+    inner: &str,
+    ctx: &Context,
+    archives: &mut ArchiveCache,
+    had_error: &mut bool,
+) -> Vec<FileItem> {
+    let path = Path::new(archive_path);
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            eprintln!("ERROR: Failed to read metadata for {:?}: {}", path, e);
+            *had_error = true;
+            return Vec::new();
+        }
+    };
+
+    if !archives.contains_key(archive_path) {
+        match archive::read_members(path) {
+            Ok(members) => archives.insert(archive_path.to_string(), members),
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                *had_error = true;
+                return Vec::new();
+            }
+        };
+    }
+    let members = &archives[archive_path];
+
+    let default_modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    let children = match archive::list_dir(members, inner, default_modified) {
+        Ok(children) => children,
+        Err(e) => {
+            eprintln!("ERROR: {} ({})", e, archive_path);
+            *had_error = true;
+            return Vec::new();
+        }
+    };
+
+    children
+        .iter()
+        .map(|member| archive_member_item(member, metadata.dev(), ctx))
+        .collect()
+}
+
+/// Entries of a directory, archive or single file. Entries that can't be
+/// parsed are reported and skipped, and had_error is set.
+fn parse_path(
+    target_path: &String,
+    ctx: &Context,
+    archives: &mut ArchiveCache,
+    had_error: &mut bool,
+) -> Vec<FileItem> {
     let mut my_paths: Vec<String> = Vec::new();
     let mut my_files: Vec<FileItem> = Vec::new();
 
+    if let Some((archive_path, inner)) = archive::split_archive_path(target_path) {
+        return parse_archive(&archive_path, &inner, ctx, archives, had_error);
+    }
+
     let path = Path::new(target_path);
-    if path.is_dir() {
-        parse_directory(path, &mut my_paths);
+    let parent_dev = if path.is_dir() {
+        parse_directory(path, &mut my_paths, had_error);
+        get_dev(path)
     } else {
         my_paths.push(target_path.to_string());
-    }
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => get_dev(parent),
+            _ => get_dev(Path::new(".")),
+        }
+    };
 
     for path in my_paths.iter() {
-        let item = match parse_file_entry(path, ctx) {
+        let item = match parse_file_entry(path, parent_dev, ctx) {
             Ok(data) => data,
             Err(e) => {
-                eprintln!("ERROR: Failed to parse file ({})", e);
-                exit(1);
+                eprintln!("ERROR: Failed to parse file {} ({})", path, e);
+                *had_error = true;
+                continue;
             }
         };
         my_files.push(item);
@@ -461,6 +715,14 @@ fn parse_mode(mode: u32) -> String {
     perm
 }
 
+fn format_name(icon: &str, name: &str) -> String {
+    if icon.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} {}", icon, name)
+    }
+}
+
 fn simple_listing(items: &Vec<FileItem>, flags: &ListingFlags) {
     for item in items {
         if item.filename.starts_with(".") && !flags.show_all {
@@ -468,28 +730,14 @@ fn simple_listing(items: &Vec<FileItem>, flags: &ListingFlags) {
             continue;
         }
         println!(
-            "{}{} {}{}",
+            "{}{}{}",
             start_color(&item.color),
-            item.icon,
-            item.filename,
+            format_name(&item.icon, &item.filename),
             stop_color()
         );
     }
 }
 
-fn get_num_width(val: u64) -> usize {
-    let mut width = 0;
-    let mut my_val = val;
-    loop {
-        width += 1;
-        my_val /= 10;
-        if my_val == 0 {
-            break;
-        }
-    }
-    width
-}
-
 fn get_human_readable(my_size: u64) -> String {
     let val: f64 = my_size as f64;
     if val > TB {
@@ -505,113 +753,286 @@ fn get_human_readable(my_size: u64) -> String {
     }
 }
 
-fn detailed_listing(items: &Vec<FileItem>, flags: &ListingFlags) {
+fn get_fs_column(item: &FileItem) -> &str {
+    if item.crosses_fs || item.is_mount_point {
+        &item.fs_info
+    } else {
+        "-"
+    }
+}
+
+fn get_caps_column(item: &FileItem) -> &str {
+    match &item.capabilities {
+        Some(caps) => caps,
+        None => "-",
+    }
+}
+
+/// Default long listing columns, the optional ones depend on the flags
+fn default_columns(flags: &ListingFlags) -> Vec<Column> {
+    let mut columns = vec![Column::Perm];
+    if flags.show_attrs {
+        columns.push(Column::Attrs);
+    }
+    columns.extend([
+        Column::Links,
+        Column::User,
+        Column::Group,
+        Column::Size,
+        Column::Mtime,
+    ]);
+    if flags.show_fs {
+        columns.push(Column::Fs);
+    }
+    if flags.show_caps {
+        columns.push(Column::Caps);
+    }
+    columns.push(Column::Name);
+    columns
+}
+
+/// Plain text value of a column, as used for exports and width calculations
+fn get_cell(item: &FileItem, column: Column, flags: &ListingFlags, dir: &str) -> String {
+    match column {
+        Column::Perm => format!("{}{}", item.ftype, parse_mode(item.mode)),
+        Column::Attrs => match item.inode_flags {
+            Some(inode_flags) => attrs::format_inode_flags(inode_flags),
+            None => "---".to_string(),
+        },
+        Column::Links => item.nlink.to_string(),
+        Column::User => item.user.to_owned(),
+        Column::Group => item.group.to_owned(),
+        Column::Size => {
+            if flags.human_readable {
+                item.human_readable_size.to_owned()
+            } else {
+                item.filesize.to_string()
+            }
+        }
+        Column::Mtime => item.modified.to_owned(),
+        Column::Fs => get_fs_column(item).to_string(),
+        Column::Caps => get_caps_column(item).to_string(),
+        Column::Name => item.filename.to_owned(),
+        Column::Target => item.symlink_path.to_owned(),
+        Column::Dir => dir.to_string(),
+    }
+}
+
+/// Name column for the terminal with icons, colors, symlink target and
+/// mount point marker. Returns the text and its display width. The symlink
+/// target is left out when it has a column of its own.
+fn get_styled_name(item: &FileItem, flags: &ListingFlags) -> (String, usize) {
+    let name = format_name(&item.icon, &item.filename);
+    let mut width = name.chars().count();
+    let mut styled: String;
+
+    if item.ftype == 'l' && !flags.columns.contains(&Column::Target) {
+        // we need to handle colors a bit different
+        let target = format_name(&item.icon, &item.symlink_path);
+        width += 4 + target.chars().count();
+        styled = format!(
+            "{}{}{} -> {}{}{}",
+            start_color("#09bfc9"),
+            name,
+            stop_color(),
+            start_color(&item.color),
+            target,
+            stop_color()
+        );
+    } else if item.ftype == 'l' {
+        styled = format!("{}{}{}", start_color("#09bfc9"), name, stop_color());
+    } else {
+        styled = format!("{}{}{}", start_color(&item.color), name, stop_color());
+    }
+
+    if flags.show_fs && item.is_mount_point {
+        width += 6;
+        styled.push_str(&format!(
+            " {}[mnt]{}",
+            start_color(MOUNT_POINT_COLOR),
+            stop_color()
+        ));
+    }
+
+    (styled, width)
+}
+
+fn detailed_listing(items: &[FileItem], flags: &ListingFlags, dir: &str) {
+    let visible: Vec<&FileItem> = items
+        .iter()
+        .filter(|item| !item.filename.starts_with(".") || flags.show_all)
+        .collect();
+
+    if flags.format != OutputFormat::Table {
+        for item in visible {
+            let cells: Vec<String> = flags
+                .columns
+                .iter()
+                .map(|column| get_cell(item, *column, flags, dir))
+                .collect();
+            println!("{}", columns::format_row(&cells, flags.format));
+        }
+        return;
+    }
+
     // first traverse the filelist to get max widths
-    let mut max_size_width: usize = 0;
-    let mut max_human_size_width: usize = 0;
-    let mut max_link_width: usize = 0;
-    let mut max_user_width: usize = 0;
-    let mut max_group_width: usize = 0;
-    for item in items {
-        if item.filename.starts_with(".") && !flags.show_all {
-            // skip hidden file
-            continue;
+    let mut max_widths: Vec<usize> = vec![0; flags.columns.len()];
+    for item in visible.iter() {
+        for (idx, column) in flags.columns.iter().enumerate() {
+            let width = if *column == Column::Name {
+                get_styled_name(item, flags).1
+            } else {
+                get_cell(item, *column, flags, dir).chars().count()
+            };
+            if width > max_widths[idx] {
+                max_widths[idx] = width;
+            }
         }
-        if flags.human_readable {
-            let human_width = item.human_readable_size.len();
-            if human_width > max_human_size_width {
-                max_human_size_width = human_width;
+    }
+
+    for item in visible {
+        let mut line = String::new();
+        for (idx, column) in flags.columns.iter().enumerate() {
+            let is_last = idx + 1 == flags.columns.len();
+            let width = max_widths[idx];
+
+            let (text, text_width) = match column {
+                Column::Name => get_styled_name(item, flags),
+                Column::Perm if item.capabilities.is_some() => {
+                    // highlight setcap binaries
+                    let perms = get_cell(item, *column, flags, dir);
+                    let perms_width = perms.chars().count();
+                    let styled = format!("{}{}{}", start_color(SETCAP_COLOR), perms, stop_color());
+                    (styled, perms_width)
+                }
+                _ => {
+                    let cell = get_cell(item, *column, flags, dir);
+                    let cell_width = cell.chars().count();
+                    (cell, cell_width)
+                }
+            };
+            let padding = " ".repeat(width.saturating_sub(text_width));
+
+            if column.is_right_aligned() {
+                line.push_str(&padding);
+                line.push_str(&text);
+            } else {
+                line.push_str(&text);
+                if !is_last {
+                    line.push_str(&padding);
+                }
             }
-        } else {
-            let size_width = get_num_width(item.filesize);
-            if size_width > max_size_width {
-                max_size_width = size_width;
+            if !is_last {
+                line.push(' ');
             }
         }
-        let size_link = get_num_width(item.sym_metadata.nlink());
-        if size_link > max_link_width {
-            max_link_width = size_link;
-        }
-        let user_len = item.user.len();
-        if user_len > max_user_width {
-            max_user_width = user_len;
+        println!("{}", line);
+    }
+}
+
+fn sort_items(items: &mut [FileItem], flags: &ListingFlags) {
+    if flags.sort_by_time {
+        // sort by modified time
+        if flags.reverse_sort {
+            // reverse order
+            items.sort_by_key(|a| Reverse(a.last_modified));
+        } else {
+            items.sort_by_key(|a| a.last_modified);
         }
-        let group_len = item.group.len();
-        if group_len > max_group_width {
-            max_group_width = group_len;
+    } else {
+        // sort by filename
+        if flags.reverse_sort {
+            // reverse order
+            items.sort_by(|a, b| b.filename.cmp(&a.filename));
+        } else {
+            items.sort_by(|a, b| a.filename.cmp(&b.filename));
         }
     }
+}
+
+/// Collect the subdirectories to descend into for a recursive listing.
+/// Symlinks are not followed, and with one_file_system set we stay on the
+/// device the walk started on.
+fn get_subdirectories(
+    target_path: &str,
+    items: &[FileItem],
+    root_dev: u64,
+    flags: &ListingFlags,
+) -> Vec<String> {
+    let archive_target = archive::split_archive_path(target_path);
+    let mut subdirs: Vec<String> = Vec::new();
     for item in items {
         if item.filename.starts_with(".") && !flags.show_all {
-            // skip hidden file
             continue;
         }
-
-        // start with filetype
-        let ftype: char;
-        let mode = item.mode;
-        let mut is_symlink = false;
-
-        if item.sym_metadata.is_dir() {
-            ftype = 'd';
-        } else if item.sym_metadata.is_symlink() {
-            ftype = 'l';
-            is_symlink = true;
-        } else if item.abs_metadata.file_type().is_char_device() {
-            ftype = 'c';
-        } else if item.sym_metadata.file_type().is_block_device() {
-            ftype = 'b';
-        } else {
-            ftype = '-';
+        if item.ftype != 'd' {
+            continue;
         }
-        let perms = parse_mode(mode);
-
-        print!("{}{} ", ftype, perms);
-
-        let my_links = item.sym_metadata.nlink();
-        print!("{my_links:>max_link_width$} ");
-
-        let user = &item.user;
-        let group = &item.group;
-        print!("{user:>max_user_width$} {group:>max_group_width$} ");
+        if flags.one_file_system && item.dev != root_dev {
+            continue;
+        }
+        if let Some((archive_path, inner)) = &archive_target {
+            subdirs.push(archive::join_archive_path(
+                archive_path,
+                inner,
+                &item.filename,
+            ));
+            continue;
+        }
+        let path = Path::new(target_path).join(&item.filename);
+        if let Some(path) = path.to_str() {
+            subdirs.push(path.to_string());
+        }
+    }
+    subdirs
+}
 
-        if flags.human_readable {
-            let my_human_size = &item.human_readable_size;
-            print!("{my_human_size:>max_human_size_width$} ");
-        } else {
-            let my_size = item.filesize;
-            print!("{my_size:>max_size_width$} ");
-        }
-
-        print!("{} ", item.modified);
-
-        if is_symlink {
-            // we need to handle colors a bit different
-            print!(
-                "{}{} {}{}",
-                start_color("#09bfc9"),
-                item.icon,
-                item.filename,
-                stop_color()
-            );
-            print!(
-                " -> {}{} {}{}",
-                start_color(&item.color),
-                item.icon,
-                item.symlink_path,
-                stop_color()
-            );
-        } else {
-            print!(
-                "{}{} {}{}",
-                start_color(&item.color),
-                item.icon,
-                item.filename,
-                stop_color()
-            );
+/// Apparent size of a directory and everything below it, like du -s
+/// --apparent-size. Symlinks are not followed, hard links are counted once,
+/// and with one_file_system set the walk stays on root_dev like du -x.
+fn get_total_size(path: &Path, root_dev: u64, flags: &ListingFlags) -> u64 {
+    let mut total: u64 = 0;
+    let mut seen: HashSet<(u64, u64)> = HashSet::new();
+    let mut pending: Vec<PathBuf> = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let metadata = match path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                eprintln!("ERROR: Failed to read metadata for {:?}: {}", path, e);
+                continue;
+            }
+        };
+        if flags.one_file_system && metadata.dev() != root_dev {
+            continue;
+        }
+        if metadata.nlink() > 1
+            && !metadata.is_dir()
+            && !seen.insert((metadata.dev(), metadata.ino()))
+        {
+            continue;
+        }
+        total += metadata.len();
+        if !metadata.is_dir() {
+            continue;
         }
+        match fs::read_dir(&path) {
+            Ok(entries) => pending.extend(entries.flatten().map(|entry| entry.path())),
+            Err(e) => eprintln!("ERROR: Failed to read directory ({:?})->({:?})", path, e),
+        }
+    }
+    total
+}
 
-        println!();
+/// Replace the size of the directories among items with the total size of
+/// their contents
+fn aggregate_sizes(target_path: &str, items: &mut [FileItem], root_dev: u64, flags: &ListingFlags) {
+    for item in items.iter_mut() {
+        if item.ftype != 'd' {
+            continue;
+        }
+        let path = Path::new(target_path).join(&item.filename);
+        item.filesize = get_total_size(&path, root_dev, flags);
+        item.human_readable_size = get_human_readable(item.filesize);
     }
 }
 
@@ -623,7 +1044,31 @@ fn show_help() {
     println!("    -l       Show long listing");
     println!("    -h       Show human readable sizes");
     println!("    -t       Sort by last modified time");
-    println!("    -r       Reverse sort\n");
+    println!("    -r       Reverse sort");
+    println!("    -R       List subdirectories recursively");
+    println!("    --fs     Show filesystem type and source for mount crossings");
+    println!("    --one-file-system");
+    println!("             Do not descend into directories on other filesystems");
+    println!("    --total-size");
+    println!("             Show the total size of the contents of directories");
+    println!("    --caps   Show file capabilities");
+    println!("    --attrs  Show immutable/append-only/nodump inode flags");
+    println!("    --icons=auto|always|never");
+    println!("             When to show icons (auto: only on a terminal)");
+    println!("    --icon-set=nerd|emoji|ascii");
+    println!("             Icon set, emoji and ascii do not need a Nerd Font");
+    println!("    --theme=dark|light");
+    println!("             Color theme for file icons");
+    println!("    --icon-map FILE");
+    println!("             Extra key:icon[:#rrggbb] mappings, key is a filename,");
+    println!("             an extension or <dir>. Default ~/.config/myls/icons");
+    println!("    --columns=COL,...");
+    println!("             Long listing columns: perm,attrs,links,user,group,size,");
+    println!("             mtime,fs,caps,name,target,dir");
+    println!("    --format=table|csv|tsv|markdown");
+    println!("             Long listing output format\n");
+    println!(".tar, .tar.gz, .tgz and .zip files are listed as directories,");
+    println!("use ARCHIVE:DIR to list a directory inside the archive.\n");
 }
 
 /*
@@ -654,6 +1099,55 @@ fn main() {
     if args.reverse {
         ctx.flags.reverse_sort = true;
     }
+    if args.recursive {
+        ctx.flags.recursive = true;
+    }
+    if args.show_fs {
+        ctx.flags.show_fs = true;
+    }
+    if args.one_file_system {
+        ctx.flags.one_file_system = true;
+    }
+    if args.total_size {
+        ctx.flags.total_size = true;
+    }
+    if args.caps {
+        ctx.flags.show_caps = true;
+    }
+    if args.attrs {
+        ctx.flags.show_attrs = true;
+    }
+    if !args.columns.is_empty() {
+        // make sure the data for the optional columns gets loaded
+        if args.columns.contains(&Column::Fs) {
+            ctx.flags.show_fs = true;
+        }
+        if args.columns.contains(&Column::Caps) {
+            ctx.flags.show_caps = true;
+        }
+        if args.columns.contains(&Column::Attrs) {
+            ctx.flags.show_attrs = true;
+        }
+        ctx.flags.columns = args.columns;
+        ctx.flags.show_details = true;
+    } else {
+        ctx.flags.columns = default_columns(&ctx.flags);
+    }
+    if args.format != OutputFormat::Table {
+        ctx.flags.format = args.format;
+        ctx.flags.show_details = true;
+    }
+    ctx.icons.configure(args.icons, args.icon_set, args.theme);
+    match args.icon_map {
+        Some(map_path) => ctx.icons.load_map(Path::new(&map_path)),
+        None => {
+            if let Some(map_path) = icons::default_map_path() {
+                if map_path.exists() {
+                    ctx.icons.load_map(&map_path);
+                }
+            }
+        }
+    }
     if args.help {
         show_help();
         exit(0);
@@ -670,12 +1164,42 @@ fn main() {
     map_ids("/etc/passwd", &mut ctx.uid_map);
     map_ids("/etc/group", &mut ctx.gid_map);
 
+    if ctx.flags.show_fs {
+        ctx.mounts = MountTable::load();
+    }
+
+    // exports get a single header row instead of a heading per path
+    let exporting = ctx.flags.format != OutputFormat::Table;
+    let show_headers = (paths_to_parse.len() > 1 || ctx.flags.recursive) && !exporting;
     let mut add_path_separator = false;
-    for target_path in paths_to_parse.iter() {
-        let mut my_files: Vec<FileItem> = parse_path(target_path, &ctx);
+    let mut had_error = false;
+    let mut archives = ArchiveCache::new();
+    if exporting {
+        println!(
+            "{}",
+            columns::format_header(&ctx.flags.columns, ctx.flags.format)
+        );
+    }
+
+    // paths are popped from the back, so keep them in reverse order
+    let mut pending: Vec<(String, u64)> = paths_to_parse
+        .iter()
+        .rev()
+        .map(|path| (path.to_string(), get_target_dev(path)))
+        .collect();
+
+    while let Some((target_path, root_dev)) = pending.pop() {
+        let mut my_files: Vec<FileItem> =
+            parse_path(&target_path, &ctx, &mut archives, &mut had_error);
+        let is_listable =
+            Path::new(&target_path).is_dir() || archive::split_archive_path(&target_path).is_some();
+        // members of archives have no directory on disk to walk
+        if ctx.flags.total_size && Path::new(&target_path).is_dir() {
+            aggregate_sizes(&target_path, &mut my_files, root_dev, &ctx.flags);
+        }
 
         // Output the contents
-        if paths_to_parse.len() > 1 {
+        if show_headers {
             // we have multiple paths so prepend dir
             if add_path_separator {
                 println!();
@@ -684,29 +1208,23 @@ fn main() {
             add_path_separator = true;
         }
 
-        // Sort files
-        if ctx.flags.sort_by_time {
-            // sort by modified time
-            if ctx.flags.reverse_sort {
-                // reverse order
-                my_files.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
-            } else {
-                my_files.sort_by(|a, b| a.last_modified.cmp(&b.last_modified));
-            }
-        } else {
-            // sort by filename
-            if ctx.flags.reverse_sort {
-                // reverse order
-                my_files.sort_by(|a, b| b.filename.cmp(&a.filename));
-            } else {
-                my_files.sort_by(|a, b| a.filename.cmp(&b.filename));
-            }
-        }
+        sort_items(&mut my_files, &ctx.flags);
 
         if ctx.flags.show_details {
-            detailed_listing(&my_files, &ctx.flags);
+            detailed_listing(&my_files, &ctx.flags, &target_path);
         } else {
             simple_listing(&my_files, &ctx.flags);
         }
+
+        if ctx.flags.recursive && is_listable {
+            let subdirs = get_subdirectories(&target_path, &my_files, root_dev, &ctx.flags);
+            for subdir in subdirs.into_iter().rev() {
+                pending.push((subdir, root_dev));
+            }
+        }
+    }
+
+    if had_error {
+        exit(1);
     }
 }
@@ -0,0 +1,224 @@
+use std::fs;
+use std::path::Path;
+
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+
+#[derive(Debug, Clone)]
+pub struct MountEntry {
+    pub major: u32,
+    pub minor: u32,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub source: String,
+}
+
+impl MountEntry {
+    /// Short description used in the fs column, ie: ext4:/dev/sda1
+    pub fn describe(&self) -> String {
+        format!("{}:{}", self.fs_type, self.source)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MountTable {
+    entries: Vec<MountEntry>,
+}
+
+impl MountTable {
+    pub fn new() -> MountTable {
+        MountTable {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Load the mount table of the current process. On failure we report the
+    /// problem and return an empty table so the listing can still go on.
+    pub fn load() -> MountTable {
+        let data = match fs::read_to_string(MOUNTINFO_PATH) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Failed to read {} file. Reason: {}", MOUNTINFO_PATH, e);
+                return MountTable::new();
+            }
+        };
+
+        let mut table = MountTable::new();
+        for line in data.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            match parse_mountinfo_line(line) {
+                Some(entry) => table.entries.push(entry),
+                None => eprintln!("Failed to parse mountinfo line [{}]", line),
+            }
+        }
+        table
+    }
+
+    /// Find the mount backing a given device number. Later entries shadow
+    /// earlier ones, so search from the end.
+    pub fn find_by_dev(&self, dev: u64) -> Option<&MountEntry> {
+        let major = libc::major(dev);
+        let minor = libc::minor(dev);
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.major == major && entry.minor == minor)
+    }
+
+    /// Find the mount containing a path using the longest mount point prefix.
+    /// Used as fallback for filesystems (like btrfs subvolumes) that report a
+    /// device number not present in mountinfo.
+    pub fn find_by_path(&self, path: &Path) -> Option<&MountEntry> {
+        let mut found: Option<&MountEntry> = None;
+        for entry in self.entries.iter() {
+            if !path.starts_with(&entry.mount_point) {
+                continue;
+            }
+            match found {
+                Some(current) if current.mount_point.len() > entry.mount_point.len() => {}
+                _ => found = Some(entry),
+            }
+        }
+        found
+    }
+
+    pub fn is_mount_point(&self, path: &Path) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| Path::new(&entry.mount_point) == path)
+    }
+}
+
+/// Parse a single line of /proc/self/mountinfo
+///
+/// 36 35 98:0 /mnt1 /mnt/parent rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+/// (1)(2)(3)   (4)   (5)      (6)      (7)    (8) (9)   (10)         (11)
+///
+/// Field (7) is a variable list of optional fields terminated by a single '-'.
+fn parse_mountinfo_line(line: &str) -> Option<MountEntry> {
+    let cols: Vec<&str> = line.split(' ').collect();
+    if cols.len() < 10 {
+        return None;
+    }
+
+    let (major, minor) = cols[2].split_once(':')?;
+    let major: u32 = major.parse().ok()?;
+    let minor: u32 = minor.parse().ok()?;
+    let mount_point = unescape_octal(cols[4]);
+
+    let separator = cols.iter().skip(6).position(|col| *col == "-")? + 6;
+    if cols.len() < separator + 3 {
+        return None;
+    }
+
+    Some(MountEntry {
+        major,
+        minor,
+        mount_point,
+        fs_type: cols[separator + 1].to_string(),
+        source: unescape_octal(cols[separator + 2]),
+    })
+}
+
+/// mountinfo escapes space, tab, newline and backslash as \ooo octal values
+fn unescape_octal(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'\\' {
+            if let Some(octal) = value.get(idx + 1..idx + 4) {
+                if let Ok(byte) = u8::from_str_radix(octal, 8) {
+                    out.push(byte);
+                    idx += 4;
+                    continue;
+                }
+            }
+        }
+        out.push(bytes[idx]);
+        idx += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape_space() {
+        assert_eq!(unescape_octal("/mnt/my\\040disk"), "/mnt/my disk");
+    }
+
+    #[test]
+    fn test_unescape_tab_newline_backslash() {
+        assert_eq!(unescape_octal("a\\011b\\012c\\134d"), "a\tb\nc\\d");
+    }
+
+    #[test]
+    fn test_unescape_invalid() {
+        // not an octal value, or cut short at the end
+        assert_eq!(unescape_octal("a\\09b"), "a\\09b");
+        assert_eq!(unescape_octal("a\\04"), "a\\04");
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let line =
+            "36 35 98:0 /mnt1 /mnt/parent rw,noatime master:1 - ext3 /dev/root rw,errors=continue";
+        let entry = parse_mountinfo_line(line).unwrap();
+        assert_eq!(entry.major, 98);
+        assert_eq!(entry.minor, 0);
+        assert_eq!(entry.mount_point, "/mnt/parent");
+        assert_eq!(entry.fs_type, "ext3");
+        assert_eq!(entry.source, "/dev/root");
+    }
+
+    #[test]
+    fn test_parse_line_escaped_path() {
+        let line = "41 25 8:17 / /media/usb\\040stick rw,relatime shared:7 - vfat /dev/sdb1 rw";
+        let entry = parse_mountinfo_line(line).unwrap();
+        assert_eq!(entry.mount_point, "/media/usb stick");
+        assert_eq!(entry.describe(), "vfat:/dev/sdb1");
+    }
+
+    #[test]
+    fn test_parse_line_no_optional_fields() {
+        let line = "25 28 0:6 / /dev rw,relatime - devtmpfs devtmpfs rw,mode=755";
+        let entry = parse_mountinfo_line(line).unwrap();
+        assert_eq!(entry.mount_point, "/dev");
+        assert_eq!(entry.fs_type, "devtmpfs");
+    }
+
+    #[test]
+    fn test_parse_line_invalid() {
+        assert!(parse_mountinfo_line("").is_none());
+        // missing the separator before the filesystem type
+        assert!(parse_mountinfo_line(
+            "36 35 98:0 /mnt1 /mnt rw,noatime master:1 ext3 /dev/root rw"
+        )
+        .is_none());
+        assert!(
+            parse_mountinfo_line("36 35 98-0 /mnt1 /mnt rw,noatime - ext3 /dev/root rw").is_none()
+        );
+    }
+
+    #[test]
+    fn test_find_by_path() {
+        let line_root = "1 0 8:1 / / rw - ext4 /dev/sda1 rw";
+        let line_home = "2 1 8:2 / /home rw - ext4 /dev/sda2 rw";
+        let table = MountTable {
+            entries: vec![
+                parse_mountinfo_line(line_root).unwrap(),
+                parse_mountinfo_line(line_home).unwrap(),
+            ],
+        };
+        let entry = table.find_by_path(Path::new("/home/user/file")).unwrap();
+        assert_eq!(entry.source, "/dev/sda2");
+        let entry = table.find_by_path(Path::new("/etc/passwd")).unwrap();
+        assert_eq!(entry.source, "/dev/sda1");
+        assert!(table.is_mount_point(Path::new("/home")));
+        assert!(!table.is_mount_point(Path::new("/home/user")));
+    }
+}